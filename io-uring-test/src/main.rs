@@ -71,25 +71,45 @@ fn test<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     };
 
     tests::queue::test_nop(&mut ring, &test)?;
+    tests::queue::test_nop_inject_result(&mut ring, &test)?;
+    tests::queue::test_nop_file_buffer(&mut ring, &test)?;
     tests::queue::test_setup_no_sqarray(&mut ring, &test)?;
+    tests::queue::test_setup_no_mmap(&mut ring, &test)?;
+    tests::queue::test_setup_registered_fd_only(&mut ring, &test)?;
+    tests::queue::test_setup_hybrid_iopoll(&mut ring, &test)?;
+    tests::queue::test_resize_rings(&mut ring, &test)?;
     tests::queue::test_queue_split(&mut ring, &test)?;
     tests::queue::test_completion_status(&mut ring, &test)?;
     tests::queue::test_debug_print(&mut ring, &test)?;
+    tests::queue::test_op_registry(&mut ring, &test)?;
+    tests::queue::test_chain_link_timeout(&mut ring, &test)?;
+    tests::queue::test_chain_push_all_or_nothing(&mut ring, &test)?;
     tests::queue::test_msg_ring_data(&mut ring, &test)?;
     tests::queue::test_msg_ring_send_fd(&mut ring, &test)?;
+    tests::queue::test_send_msg_ring(&mut ring, &test)?;
 
     tests::queue::test_batch(&mut ring, &test)?;
 
     // register
     tests::register::test_register_files_sparse(&mut ring, &test)?;
+    tests::register::test_register_file_alloc_range(&mut ring, &test)?;
+    tests::register::test_register_files_tags(&mut ring, &test)?;
+    tests::register::test_fixed_file_table(&mut ring, &test)?;
     tests::register::test_register_ring_fd(&mut ring, &test)?;
     tests::register_buffers::test_register_buffers(&mut ring, &test)?;
+    tests::register_buffers::test_registered_buffers(&mut ring, &test)?;
+    tests::register_buffers::test_registered_buffers_owned(&mut ring, &test)?;
     tests::register_buffers::test_register_buffers_update(&mut ring, &test)?;
     tests::register_buffers::test_register_buffers_clone(&test)?;
     tests::register_buf_ring::test_register_buf_ring(&mut ring, &test)?;
+    tests::register_buf_ring::test_buf_ring(&mut ring, &test)?;
+    tests::register_buf_ring::test_buf_ring_select_op(&mut ring, &test)?;
+    tests::register_buf_ring::test_buf_ring_incremental(&mut ring, &test)?;
+    tests::register_buf_ring::test_buf_ring_head(&mut ring, &test)?;
     tests::register_sync_cancel::test_register_sync_cancel(&mut ring, &test)?;
     tests::register_sync_cancel::test_register_sync_cancel_unsubmitted(&mut ring, &test)?;
     tests::register_sync_cancel::test_register_sync_cancel_any(&mut ring, &test)?;
+    tests::register_sync_cancel::test_register_sync_cancel_fd_opcode(&mut ring, &test)?;
     tests::register_napi::test_register_napi(&mut ring, &test)?;
 
     // async cancellation
@@ -98,6 +118,7 @@ fn test<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     tests::cancel::test_async_cancel_any(&mut ring, &test)?;
     tests::cancel::test_async_cancel_fd(&mut ring, &test)?;
     tests::cancel::test_async_cancel_fd_all(&mut ring, &test)?;
+    tests::cancel::test_async_cancel_fd_opcode(&mut ring, &test)?;
 
     // epoll
     tests::epoll::test_ready(&mut ring, &test)?;
@@ -108,6 +129,7 @@ fn test<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
 
     // fs
     tests::fs::test_file_write_read(&mut ring, &test)?;
+    tests::fs::test_file_rw_attr_pi(&mut ring, &test)?;
     tests::fs::test_pipe_read_multishot(&mut ring, &test)?;
     tests::fs::test_file_writev_readv(&mut ring, &test)?;
     tests::fs::test_pipe_fixed_writev_readv(&mut ring, &test)?;
@@ -120,6 +142,9 @@ fn test<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     tests::fs::test_file_openat2_close_file_index(&mut ring, &test)?;
     tests::fs::test_file_openat_close_file_index(&mut ring, &test)?;
     tests::fs::test_file_close(&mut ring, &test)?;
+    tests::fs::test_file_decode_results(&mut ring, &test)?;
+    tests::fs::test_file_chain_direct_descriptor(&mut ring, &test)?;
+    tests::fs::test_file_owned_buffers(&mut ring, &test)?;
     tests::fs::test_file_direct_write_read(&mut ring, &test)?;
     #[cfg(not(feature = "ci"))]
     tests::fs::test_statx(&mut ring, &test)?;
@@ -138,6 +163,8 @@ fn test<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     tests::timeout::test_timeout_abs(&mut ring, &test)?;
     tests::timeout::test_timeout_submit_args(&mut ring, &test)?;
     tests::timeout::test_timeout_submit_args_min_wait(&mut ring, &test)?;
+    tests::timeout::test_timeout_submit_registered_args(&mut ring, &test)?;
+    tests::timeout::test_timeout_submit_args_abs(&mut ring, &test)?;
     tests::timeout::test_timeout_multishot(&mut ring, &test)?;
 
     // net
@@ -146,6 +173,7 @@ fn test<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     tests::net::test_tcp_send_recv(&mut ring, &test)?;
     tests::net::test_tcp_send_bundle(&mut ring, &test)?;
     tests::net::test_tcp_zero_copy_send_recv(&mut ring, &test)?;
+    tests::net::test_tcp_zero_copy_send_tracker(&mut ring, &test)?;
     tests::net::test_tcp_zero_copy_send_fixed(&mut ring, &test)?;
     tests::net::test_tcp_sendmsg_recvmsg(&mut ring, &test)?;
     tests::net::test_tcp_zero_copy_sendmsg_recvmsg(&mut ring, &test)?;
@@ -154,6 +182,7 @@ fn test<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     tests::net::test_tcp_accept_multi(&mut ring, &test)?;
     tests::net::test_tcp_accept_multi_file_index(&mut ring, &test)?;
     tests::net::test_tcp_connect(&mut ring, &test)?;
+    tests::net::test_socket_addr_connect_accept(&mut ring, &test)?;
     tests::net::test_tcp_buffer_select(&mut ring, &test)?;
     tests::net::test_tcp_buffer_select_recvmsg(&mut ring, &test)?;
     tests::net::test_tcp_buffer_select_readv(&mut ring, &test)?;
@@ -162,6 +191,8 @@ fn test<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     tests::net::test_tcp_recv_multi_bundle(&mut ring, &test)?;
 
     tests::net::test_tcp_shutdown(&mut ring, &test)?;
+    tests::net::test_tcp_getsockopt(&mut ring, &test)?;
+    tests::net::test_tcp_siocinq_siocoutq(&mut ring, &test)?;
     tests::net::test_socket(&mut ring, &test)?;
     tests::net::test_socket_bind_listen(&mut ring, &test)?;
     tests::net::test_udp_recvmsg_multishot(&mut ring, &test)?;
@@ -177,6 +208,7 @@ fn test<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     tests::poll::test_eventfd_poll_remove(&mut ring, &test)?;
     tests::poll::test_eventfd_poll_remove_failed(&mut ring, &test)?;
     tests::poll::test_eventfd_poll_multi(&mut ring, &test)?;
+    tests::poll::test_eventfd_poll_update(&mut ring, &test)?;
 
     // pipe
     tests::pipe::test_pipe(&mut ring, &test)?;
@@ -189,6 +221,15 @@ fn test<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     // os (process)
     tests::os::test_waitid(&mut ring, &test)?;
 
+    // driver
+    tests::driver::test_driver_file_write_read(&test)?;
+    tests::driver::test_driver_owned_buffers(&test)?;
+    tests::driver::test_driver_tcp_connect_accept(&test)?;
+    tests::driver::test_driver_drop_cancel(&test)?;
+    tests::driver::test_driver_file(&test)?;
+    tests::driver::test_driver_file_fixed(&test)?;
+    tests::driver::test_driver_file_drop(&test)?;
+
     // regression test
     tests::regression::test_issue154(&mut ring, &test)?;
 
@@ -150,6 +150,7 @@ pub fn test_async_cancel_any<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     assert_eq!(cqes[0].result(), -libc::ECANCELED); // -ECANCELED
     assert_eq!(cqes[1].result(), -libc::ECANCELED);
     assert_eq!(cqes[2].result(), 2); // the number of requests cancelled
+    assert_eq!(cqes[2].decode::<opcode::AsyncCancel2>()?, 2);
 
     Ok(())
 }
@@ -254,6 +255,104 @@ pub fn test_async_cancel_fd_all<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     Ok(())
 }
 
+// Cancels all requests matching both the FD and the opcode.
+pub fn test_async_cancel_fd_opcode<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::PollAdd::CODE);
+        test.probe.is_supported(opcode::Read::CODE);
+        test.probe.is_supported(opcode::AsyncCancel2::CODE);
+        test.probe.is_supported(opcode::FutexWait::CODE); // Check if Kernel >= 6.6
+    );
+
+    println!("test async_cancel_fd_opcode");
+
+    let _fd = create_dummy_fd()?;
+    let fd = types::Fd(_fd.as_raw_fd());
+    let _other_fd = create_dummy_fd()?;
+    let other_fd = types::Fd(_other_fd.as_raw_fd());
+
+    let mut buf = [0u8; 8];
+    let poll_e = opcode::PollAdd::new(fd, libc::POLLIN as _).build();
+    let read_e = opcode::Read::new(fd, buf.as_mut_ptr(), buf.len() as _).build();
+    let other_poll_e = opcode::PollAdd::new(other_fd, libc::POLLIN as _).build();
+
+    // Cancel all polls on FD, leaving the read and the poll on the other FD alone.
+    let builder = CancelBuilder::fd(fd)
+        .with_opcode(opcode::PollAdd::CODE)
+        .all();
+    let cancel_e = opcode::AsyncCancel2::new(builder).build();
+
+    let entries = [
+        poll_e.clone().user_data(2006).into(),
+        poll_e.user_data(2007).into(),
+        read_e.user_data(2008).into(),
+        other_poll_e.user_data(2009).into(),
+        cancel_e.user_data(2010).into(),
+    ];
+    for sqe in &entries {
+        unsafe {
+            ring.submission().push(sqe).expect("queue is full");
+        }
+    }
+
+    // Wait for both polls on FD and the cancel request.
+    ring.submit_and_wait(3)?;
+
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_unstable_by_key(cqueue::Entry::user_data);
+
+    assert_eq!(cqes.len(), 3);
+
+    assert_eq!(cqes[0].user_data(), 2006);
+    assert_eq!(cqes[1].user_data(), 2007);
+    assert_eq!(cqes[2].user_data(), 2010);
+
+    assert_eq!(cqes[0].result(), -libc::ECANCELED);
+    assert_eq!(cqes[1].result(), -libc::ECANCELED);
+    assert_eq!(cqes[2].result(), 2); // the number of requests cancelled
+
+    // Cancel the poll on the other FD by opcode and user_data, then the read.
+    let cancel_poll_e = opcode::AsyncCancel2::new(
+        CancelBuilder::opcode(opcode::PollAdd::CODE).with_user_data(2009),
+    )
+    .build();
+    let cancel_read_e =
+        opcode::AsyncCancel2::new(CancelBuilder::opcode(opcode::Read::CODE)).build();
+
+    unsafe {
+        let mut queue = ring.submission();
+        queue
+            .push(&cancel_poll_e.user_data(2011).into())
+            .expect("queue is full");
+        queue
+            .push(&cancel_read_e.user_data(2012).into())
+            .expect("queue is full");
+    }
+
+    ring.submit_and_wait(4)?;
+
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_unstable_by_key(cqueue::Entry::user_data);
+
+    assert_eq!(cqes.len(), 4);
+
+    assert_eq!(cqes[0].user_data(), 2008);
+    assert_eq!(cqes[1].user_data(), 2009);
+    assert_eq!(cqes[2].user_data(), 2011);
+    assert_eq!(cqes[3].user_data(), 2012);
+
+    assert_eq!(cqes[0].result(), -libc::ECANCELED);
+    assert_eq!(cqes[1].result(), -libc::ECANCELED);
+    assert_eq!(cqes[2].result(), 0);
+    assert_eq!(cqes[3].result(), 0);
+
+    Ok(())
+}
+
 fn create_dummy_fd() -> anyhow::Result<File> {
     unsafe {
         let fd = libc::eventfd(0, libc::EFD_CLOEXEC);
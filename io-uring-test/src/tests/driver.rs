@@ -0,0 +1,421 @@
+use crate::Test;
+use io_uring::buf::{BufOp, IoBuf};
+use io_uring::driver::Driver;
+use io_uring::file_table::FixedFileTable;
+use io_uring::fs::File;
+use io_uring::{opcode, types, IoUring};
+use std::future::Future;
+use std::io::{Read, Write};
+use std::net::{self, TcpListener};
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Polls the inner future once, resolving to `None` if it is still pending.
+struct PollOnce<F>(F);
+
+impl<F: Future + Unpin> Future for PollOnce<F> {
+    type Output = Option<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.0).poll(cx) {
+            Poll::Ready(output) => Poll::Ready(Some(output)),
+            Poll::Pending => Poll::Ready(None),
+        }
+    }
+}
+
+pub fn test_driver_file_write_read(test: &Test) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::Write::CODE);
+        test.probe.is_supported(opcode::Read::CODE);
+    );
+
+    println!("test driver_file_write_read");
+
+    let driver = Driver::new(4)?;
+    let fd = tempfile::tempfile()?;
+    let fd = types::Fd(fd.as_raw_fd());
+
+    let text = b"The quick brown fox jumps over the lazy dog.".to_vec();
+
+    let output = driver.block_on(async {
+        let write_e = opcode::Write::new(fd, text.as_ptr(), text.len() as _).offset(0);
+        let (cqe, _text) = unsafe { driver.submit(write_e.build(), text) }.await;
+        let n = cqe.decode::<opcode::Write>()?;
+
+        let mut output = vec![0; n];
+        let read_e = opcode::Read::new(fd, output.as_mut_ptr(), n as _).offset(0);
+        let (cqe, output) = unsafe { driver.submit(read_e.build(), output) }.await;
+        assert_eq!(cqe.decode::<opcode::Read>()?, n);
+
+        Ok::<_, std::io::Error>(output)
+    })??;
+
+    assert_eq!(output, b"The quick brown fox jumps over the lazy dog.");
+    assert_eq!(driver.in_flight(), 0);
+
+    Ok(())
+}
+
+pub fn test_driver_owned_buffers(test: &Test) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::Send::CODE);
+        test.probe.is_supported(opcode::Recv::CODE);
+    );
+
+    println!("test driver_owned_buffers");
+
+    let driver = Driver::new(4)?;
+    let (tx, rx) = std::os::unix::net::UnixStream::pair()?;
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+
+    let (sent, received) = driver.block_on(async {
+        let recv = driver.submit_owned(BufOp::recv(
+            types::Fd(rx.as_raw_fd()),
+            Vec::with_capacity(64),
+        ));
+        let (res, _) = driver
+            .submit_owned(BufOp::send(types::Fd(tx.as_raw_fd()), text.to_vec()))
+            .await;
+        let (received, buf) = recv.await;
+        (res, received.map(|n| (n, buf)))
+    })?;
+
+    assert_eq!(sent?, text.len());
+    let (n, buf) = received?;
+    assert_eq!(n, text.len());
+    assert_eq!(buf, text);
+
+    // A dropped receive is cancelled, and the driver keeps its buffer until then.
+    let recv = driver.submit_owned(BufOp::recv(types::Fd(rx.as_raw_fd()), vec![0; 16]));
+    drop(recv);
+    for _ in 0..8 {
+        if driver.in_flight() == 0 {
+            break;
+        }
+        driver.block_on(unsafe { driver.submit(opcode::Nop::new().build(), ()) })?;
+    }
+    assert_eq!(driver.in_flight(), 0);
+
+    Ok(())
+}
+
+pub fn test_driver_tcp_connect_accept(test: &Test) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::Accept::CODE);
+        test.probe.is_supported(opcode::Connect::CODE);
+        test.probe.is_supported(opcode::Send::CODE);
+        test.probe.is_supported(opcode::Recv::CODE);
+    );
+
+    println!("test driver_tcp_connect_accept");
+
+    let driver = Driver::new(4)?;
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = types::SockAddr::from(listener.local_addr()?);
+
+    let socket = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::STREAM, None)?;
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+
+    let (stream, peer) = driver.block_on(async {
+        // Both requests are submitted before either is awaited.
+        let accept = unsafe {
+            driver.submit(
+                opcode::Accept::new(
+                    types::Fd(listener.as_raw_fd()),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                )
+                .build(),
+                (),
+            )
+        };
+        let addr = Box::pin(addr);
+        let connect_e = opcode::Connect::with_addr(types::Fd(socket.as_raw_fd()), addr.as_ref());
+        let connect = unsafe { driver.submit(connect_e.build(), addr) };
+
+        let (cqe, ()) = accept.await;
+        // Safety: this is the only completion of the accept, which has no `file_index`.
+        let stream = net::TcpStream::from(unsafe { cqe.decode_fd::<opcode::Accept>() }?);
+        let (cqe, _) = connect.await;
+        cqe.decode::<opcode::Connect>()?;
+
+        let buf = text.to_vec();
+        let send_e = opcode::Send::new(types::Fd(socket.as_raw_fd()), buf.as_ptr(), buf.len() as _);
+        let (cqe, _) = unsafe { driver.submit(send_e.build(), buf) }.await;
+        assert_eq!(cqe.decode::<opcode::Send>()?, text.len());
+
+        let mut buf = vec![0; text.len()];
+        let recv_e = opcode::Recv::new(
+            types::Fd(stream.as_raw_fd()),
+            buf.as_mut_ptr(),
+            buf.len() as _,
+        );
+        let (cqe, buf) = unsafe { driver.submit(recv_e.build(), buf) }.await;
+        let n = cqe.decode::<opcode::Recv>()?;
+
+        Ok::<_, std::io::Error>((stream, buf[..n].to_vec()))
+    })??;
+
+    assert_eq!(peer, text);
+    assert_eq!(
+        stream.peer_addr()?,
+        socket.local_addr()?.as_socket().unwrap()
+    );
+    assert_eq!(driver.in_flight(), 0);
+
+    Ok(())
+}
+
+pub fn test_driver_drop_cancel(test: &Test) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::Read::CODE);
+        test.probe.is_supported(opcode::AsyncCancel::CODE);
+        test.probe.is_supported(opcode::Nop::CODE);
+    );
+
+    println!("test driver_drop_cancel");
+
+    let driver = Driver::new(4)?;
+    let (mut rx, mut tx) = std::io::pipe()?;
+
+    let mut buf = vec![0; 16];
+    let read_e = opcode::Read::new(types::Fd(rx.as_raw_fd()), buf.as_mut_ptr(), buf.len() as _);
+    let read = unsafe { driver.submit(read_e.build(), buf) };
+
+    // Nothing to read yet, so the read stays pending and is cancelled when dropped.
+    let mut read = PollOnce(read);
+    assert!(driver.block_on(&mut read)?.is_none());
+    drop(read);
+    assert_eq!(driver.in_flight(), 2);
+
+    // Drive the ring until the read and its cancellation have completed.
+    for _ in 0..8 {
+        if driver.in_flight() == 0 {
+            break;
+        }
+        let (cqe, ()) =
+            driver.block_on(unsafe { driver.submit(opcode::Nop::new().build(), ()) })?;
+        cqe.decode::<opcode::Nop>()?;
+    }
+    assert_eq!(driver.in_flight(), 0);
+
+    // The data was not consumed by the cancelled read.
+    tx.write_all(b"hello")?;
+    drop(tx);
+    let mut output = Vec::new();
+    rx.read_to_end(&mut output)?;
+    assert_eq!(output, b"hello");
+
+    // The data of a cancelled request is dropped outside of the driver, so it may use it.
+    struct SubmitOnDrop(Driver);
+
+    impl Drop for SubmitOnDrop {
+        fn drop(&mut self) {
+            drop(unsafe { self.0.submit(opcode::Nop::new().build(), ()) });
+        }
+    }
+
+    let (rx, _tx) = std::io::pipe()?;
+    let mut buf = vec![0; 16];
+    let read_e = opcode::Read::new(types::Fd(rx.as_raw_fd()), buf.as_mut_ptr(), buf.len() as _);
+    let read = unsafe { driver.submit(read_e.build(), (buf, SubmitOnDrop(driver.clone()))) };
+    let mut read = PollOnce(read);
+    assert!(driver.block_on(&mut read)?.is_none());
+    drop(read);
+
+    for _ in 0..8 {
+        if driver.in_flight() == 0 {
+            break;
+        }
+        let (cqe, ()) =
+            driver.block_on(unsafe { driver.submit(opcode::Nop::new().build(), ()) })?;
+        cqe.decode::<opcode::Nop>()?;
+    }
+    assert_eq!(driver.in_flight(), 0);
+
+    Ok(())
+}
+
+pub fn test_driver_file(test: &Test) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::OpenAt2::CODE);
+        test.probe.is_supported(opcode::Write::CODE);
+        test.probe.is_supported(opcode::Read::CODE);
+        test.probe.is_supported(opcode::Fsync::CODE);
+        test.probe.is_supported(opcode::Fallocate::CODE);
+        test.probe.is_supported(opcode::Ftruncate::CODE);
+        test.probe.is_supported(opcode::Statx::CODE);
+        test.probe.is_supported(opcode::Close::CODE);
+    );
+
+    println!("test driver_file");
+
+    let driver = Driver::new(4)?;
+    let dir = tempfile::TempDir::new_in(".")?;
+    let path = dir.path().join("test-io-uring-driver-file");
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+
+    let output = driver.block_on(async {
+        let how = types::OpenHow::new()
+            .flags((libc::O_RDWR | libc::O_CREAT | libc::O_EXCL) as _)
+            .mode(0o644);
+        let file = File::open(&driver, &path, &how).await?;
+
+        let (res, _) = file.write_all_at(text.to_vec(), 4).await;
+        res?;
+        file.sync_data().await?;
+
+        let (res, output) = file.read_exact_at(Vec::with_capacity(text.len()), 4).await;
+        res?;
+
+        // Reading past the end of the file fails, keeping what was read.
+        let (res, short) = file.read_exact_at(Vec::with_capacity(16), 40).await;
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(short, &text[36..]);
+
+        // Reading into a slice past the initialized bytes leaves the buffer as it is.
+        let slice = Vec::with_capacity(64).slice(8..8 + text.len());
+        let (res, slice) = file.read_exact_at(slice, 4).await;
+        res?;
+        assert_eq!(slice.bytes_init(), 0);
+        assert!(slice.into_inner().is_empty());
+
+        file.allocate(0, 4096).await?;
+        file.set_len(1024).await?;
+        file.sync_all().await?;
+
+        #[cfg(target_env = "gnu")]
+        {
+            let statx = file.metadata().await?;
+            assert_eq!(statx.stx_size, 1024);
+            assert_eq!(statx.stx_mode as libc::mode_t & libc::S_IFMT, libc::S_IFREG);
+        }
+
+        file.close().await?;
+
+        Ok::<_, std::io::Error>(output)
+    })??;
+
+    assert_eq!(output, text);
+    assert_eq!(std::fs::metadata(&path)?.len(), 1024);
+    assert_eq!(&std::fs::read(&path)?[4..][..text.len()], text);
+
+    // A missing file is reported as such.
+    let how = types::OpenHow::new().flags(libc::O_RDONLY as _);
+    let err = driver
+        .block_on(File::open(&driver, dir.path().join("missing"), &how))?
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    assert_eq!(driver.in_flight(), 0);
+
+    Ok(())
+}
+
+pub fn test_driver_file_fixed(test: &Test) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::OpenAt2::CODE);
+        test.probe.is_supported(opcode::Write::CODE);
+        test.probe.is_supported(opcode::Read::CODE);
+        test.probe.is_supported(opcode::Fsync::CODE);
+        test.probe.is_supported(opcode::Close::CODE);
+        // Direct descriptors were added alongside `IORING_OP_MKDIRAT`.
+        test.probe.is_supported(opcode::MkDirAt::CODE);
+    );
+
+    println!("test driver_file_fixed");
+
+    let ring = IoUring::new(4)?;
+    let table = FixedFileTable::new(&ring.submitter(), 1)?;
+    let driver = Driver::with_ring(ring);
+
+    let tmp = tempfile::NamedTempFile::new()?;
+    let text = b"The quick brown fox jumps over the lazy dog.";
+
+    let output = driver.block_on(async {
+        let how = types::OpenHow::new().flags(libc::O_RDWR as _);
+        let file = File::open_fixed(&driver, tmp.path(), &how, table.reserve()?).await?;
+        assert_eq!(table.available(), 0);
+
+        let (res, _) = file.write_all_at(text.to_vec(), 0).await;
+        res?;
+        file.sync_all().await?;
+
+        let (res, output) = file.read_exact_at(vec![0; text.len()], 0).await;
+        res?;
+
+        #[cfg(target_env = "gnu")]
+        assert_eq!(
+            file.metadata().await.unwrap_err().kind(),
+            std::io::ErrorKind::Unsupported
+        );
+
+        // The slot is handed back once the file is closed.
+        file.close().await?;
+        assert_eq!(table.available(), 1);
+
+        Ok::<_, std::io::Error>(output)
+    })??;
+
+    assert_eq!(output, text);
+    assert_eq!(std::fs::read(tmp.path())?, text);
+    assert_eq!(driver.in_flight(), 0);
+
+    Ok(())
+}
+
+pub fn test_driver_file_drop(test: &Test) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::Read::CODE);
+        test.probe.is_supported(opcode::AsyncCancel::CODE);
+        test.probe.is_supported(opcode::Close::CODE);
+        test.probe.is_supported(opcode::Nop::CODE);
+    );
+
+    println!("test driver_file_drop");
+
+    let driver = Driver::new(4)?;
+
+    // Closing the file waits for the cancelled read to let go of it.
+    let (rx, _tx) = std::io::pipe()?;
+    let file = File::from_std(&driver, OwnedFd::from(rx).into());
+    driver.block_on(async {
+        let mut read = PollOnce(Box::pin(file.read_at(Vec::with_capacity(16), 0)));
+        assert!((&mut read).await.is_none());
+        drop(read);
+        assert_eq!(driver.in_flight(), 2);
+
+        file.close().await
+    })??;
+    assert_eq!(driver.in_flight(), 0);
+
+    // Dropping the file leaves it open until the cancelled read has completed.
+    let (rx, _tx) = std::io::pipe()?;
+    let fd = rx.as_raw_fd();
+    let file = File::from_std(&driver, OwnedFd::from(rx).into());
+    let mut read = PollOnce(Box::pin(file.read_at(Vec::with_capacity(16), 0)));
+    assert!(driver.block_on(&mut read)?.is_none());
+    drop(read);
+    drop(file);
+    assert_ne!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, -1);
+
+    while driver.in_flight() > 0 {
+        let (cqe, ()) =
+            driver.block_on(unsafe { driver.submit(opcode::Nop::new().build(), ()) })?;
+        cqe.decode::<opcode::Nop>()?;
+    }
+    assert_eq!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, -1);
+
+    Ok(())
+}
@@ -1,5 +1,8 @@
 use crate::utils;
 use crate::Test;
+use io_uring::buf::{BufOp, IoBuf};
+use io_uring::chain::Chain;
+use io_uring::file_table::FixedFileTable;
 use io_uring::{cqueue, opcode, squeue, types, IoUring};
 use std::ffi::CString;
 use std::fs;
@@ -27,6 +30,66 @@ pub fn test_file_write_read<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     Ok(())
 }
 
+pub fn test_file_rw_attr_pi<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::Write::CODE);
+        test.probe.is_supported(opcode::Read::CODE);
+        ring.params().is_feature_rw_attr();
+    );
+
+    println!("test file_rw_attr_pi");
+
+    // A regular file has no integrity profile, so protection information is rejected.
+
+    let fd = tempfile::tempfile()?;
+    let fd = types::Fd(fd.as_raw_fd());
+
+    let mut buf = [0x5a; 512];
+    let mut meta = [0; 8];
+    let attr = types::PiAttr::new(meta.as_mut_ptr(), meta.len() as _)
+        .flags(types::PiFlags::CHK_GUARD | types::PiFlags::CHK_REFTAG)
+        .seed(0);
+
+    let write_e = opcode::Write::new(fd, buf.as_ptr(), buf.len() as _)
+        .attr_pi(&attr)
+        .build()
+        .user_data(0x01);
+    let read_e = opcode::Read::new(fd, buf.as_mut_ptr(), buf.len() as _)
+        .attr_pi(&attr)
+        .build()
+        .user_data(0x02);
+
+    unsafe {
+        let mut queue = ring.submission();
+        queue.push(&write_e.into()).expect("queue is full");
+        queue.push(&read_e.into()).expect("queue is full");
+    }
+
+    ring.submit_and_wait(2)?;
+
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_by_key(|cqe| cqe.user_data());
+
+    assert_eq!(cqes.len(), 2);
+    for cqe in &cqes {
+        assert!(
+            [-libc::EOPNOTSUPP, -libc::EINVAL].contains(&cqe.result()),
+            "unexpected result {}",
+            cqe.result()
+        );
+    }
+
+    // Nothing was written either.
+
+    assert_eq!(unsafe { libc::lseek(fd.0, 0, libc::SEEK_END) }, 0);
+
+    Ok(())
+}
+
 pub fn test_pipe_read_multishot<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     test: &Test,
@@ -875,6 +938,227 @@ pub fn test_file_close<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     Ok(())
 }
 
+pub fn test_file_decode_results<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::OpenAt::CODE);
+        test.probe.is_supported(opcode::Write::CODE);
+        test.probe.is_supported(opcode::Read::CODE);
+        test.probe.is_supported(opcode::Close::CODE);
+        test.probe.is_supported(opcode::PollAdd::CODE);
+        test.probe.is_supported(opcode::Timeout::CODE);
+        test.probe.is_supported(opcode::ProvideBuffers::CODE);
+    );
+
+    use tempfile::tempdir;
+
+    println!("test file_decode_results");
+
+    let dir = tempdir()?;
+    let path = dir.path().join("test-io-uring-decode-results");
+    let path = CString::new(path.as_os_str().as_bytes())?;
+
+    let mut submit_one = |entry: squeue::Entry| -> anyhow::Result<cqueue::Entry> {
+        unsafe {
+            ring.submission()
+                .push(&entry.user_data(0x2a).into())
+                .expect("queue is full");
+        }
+        ring.submit_and_wait(1)?;
+        let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+        assert_eq!(cqe.user_data(), 0x2a);
+        Ok(cqe)
+    };
+
+    let open_e = opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), path.as_ptr())
+        .flags(libc::O_CREAT | libc::O_RDWR)
+        .mode(0o644);
+    // Safety: this is the only completion of the open, which has no `file_index`.
+    let file = unsafe { submit_one(open_e.build())?.decode_fd::<opcode::OpenAt>() }?;
+    // Closed through the ring below.
+    let fd = types::Fd(file.into_raw_fd());
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+    let mut output = vec![0; text.len()];
+
+    let write_e = opcode::Write::new(fd, text.as_ptr(), text.len() as _);
+    let cqe = submit_one(write_e.build())?;
+    assert_eq!(cqe.decode::<opcode::Write>()?, text.len());
+
+    let read_e = opcode::Read::new(fd, output.as_mut_ptr(), output.len() as _);
+    let cqe = submit_one(read_e.build())?;
+    assert_eq!(cqe.decode::<opcode::Read>()?, text.len());
+    assert_eq!(output, text);
+
+    // A read picking its buffer from a group reports which one it used.
+    let mut bufs = vec![0u8; 2 * text.len()];
+    let provide_e = opcode::ProvideBuffers::new(bufs.as_mut_ptr(), text.len() as _, 2, 0x7, 3);
+    assert!(submit_one(provide_e.build())?.result() >= 0);
+
+    let read_e = opcode::Read::new(fd, std::ptr::null_mut(), text.len() as _)
+        .offset(4)
+        .buf_group(0x7);
+    let cqe = submit_one(read_e.build().flags(squeue::Flags::BUFFER_SELECT))?;
+    let read = cqe.decode_selected()?;
+    assert_eq!(read.len(), text.len() - 4);
+    let bid = read.buf_id().expect("no buffer selected");
+    let buf = &bufs[(bid as usize - 3) * text.len()..][..read.len()];
+    assert_eq!(buf, &text[4..]);
+
+    let cqe = submit_one(opcode::Close::new(fd).build())?;
+    cqe.decode::<opcode::Close>()?;
+
+    // The file descriptor is gone, so closing it again fails.
+    let cqe = submit_one(opcode::Close::new(fd).build())?;
+    let err = cqe.decode::<opcode::Close>().unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+
+    let (_rx, tx) = ::std::io::pipe()?;
+    let poll_e = opcode::PollAdd::new(types::Fd(tx.as_raw_fd()), libc::POLLOUT as _);
+    let mask = submit_one(poll_e.build())?.decode::<opcode::PollAdd>()?;
+    assert_eq!(mask & libc::POLLOUT as u32, libc::POLLOUT as u32);
+
+    // An expired timeout is not an error.
+    let ts = types::Timespec::new().nsec(1_000_000);
+    let cqe = submit_one(opcode::Timeout::new(&ts).build())?;
+    assert_eq!(cqe.result(), -libc::ETIME);
+    cqe.decode::<opcode::Timeout>()?;
+
+    Ok(())
+}
+
+pub fn test_file_chain_direct_descriptor<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::OpenAt::CODE);
+        test.probe.is_supported(opcode::Read::CODE);
+        test.probe.is_supported(opcode::Close::CODE);
+        ring.params().is_feature_linked_file();
+    );
+
+    println!("test file_chain_direct_descriptor");
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+    let mut tmp = tempfile::NamedTempFile::new()?;
+    tmp.write_all(text)?;
+    let path = CString::new(tmp.path().as_os_str().as_bytes())?;
+
+    let table = FixedFileTable::new(&ring.submitter(), 1)?;
+    let file = table.reserve()?;
+    let mut output = vec![0; text.len()];
+
+    let open_e = opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), path.as_ptr())
+        .flags(libc::O_RDONLY)
+        .file_index(Some(file.dest()));
+    let read_e = opcode::Read::new(&file, output.as_mut_ptr(), output.len() as _);
+    let closing = file.close();
+
+    // The file is closed even if reading it fails.
+    let chain = Chain::new(open_e.build())
+        .then(read_e.build())
+        .then_hard(closing.entry());
+
+    let mut completions =
+        unsafe { chain.push(&mut ring.submission(), 0x100) }.expect("queue is full");
+
+    ring.submit_and_wait(3)?;
+
+    for cqe in ring.completion().map(Into::<cqueue::Entry>::into) {
+        let step = completions
+            .complete(&cqe)
+            .expect("completion outside the chain");
+        assert!(!step.is_link_timeout());
+        assert!(!step.is_cancelled());
+    }
+
+    assert!(completions.is_done());
+    assert_eq!(
+        completions.results(),
+        &[Some(0), Some(text.len() as i32), Some(0)]
+    );
+    assert_eq!(output, text);
+
+    closing.complete(completions.results()[2].unwrap());
+    assert_eq!(table.available(), 1);
+
+    Ok(())
+}
+
+pub fn test_file_owned_buffers<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::Write::CODE);
+        test.probe.is_supported(opcode::Read::CODE);
+    );
+
+    println!("test file_owned_buffers");
+
+    let fd = tempfile::tempfile()?;
+    let fd = types::Fd(fd.as_raw_fd());
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+
+    let (res, buf) = utils::complete_owned(ring, BufOp::write(fd, text.to_vec(), 0))?;
+    assert_eq!(res?, text.len());
+    assert_eq!(buf, text);
+
+    // A read fills the capacity of a `Vec` and updates its length.
+    let (res, buf) = utils::complete_owned(ring, BufOp::read(fd, Vec::with_capacity(64), 0))?;
+    assert_eq!(res?, text.len());
+    assert_eq!(buf, text);
+
+    // Only the sliced range is read into.
+    let buf = vec![b'.'; 16].into_boxed_slice().slice(4..9);
+    let (res, buf) = utils::complete_owned(ring, BufOp::read(fd, buf, 16))?;
+    assert_eq!(res?, 5);
+    assert_eq!(&buf.into_inner()[..], b"....fox j.......");
+
+    // A read into a slice extends the initialized part of the buffer it continues...
+    let mut buf = Vec::with_capacity(16);
+    buf.extend_from_slice(b"The");
+    let (res, buf) = utils::complete_owned(ring, BufOp::read(fd, buf.slice(3..9), 3))?;
+    assert_eq!(res?, 6);
+    assert_eq!(buf.into_inner(), b"The quick");
+
+    // ...but not if it starts past it, as the bytes in between are not initialized.
+    let buf = Vec::with_capacity(100).slice(50..);
+    let (res, buf) = utils::complete_owned(ring, BufOp::read(fd, buf, 0))?;
+    assert_eq!(res?, text.len());
+    assert_eq!(buf.bytes_init(), 0);
+    assert!(buf.into_inner().is_empty());
+
+    // Only the initialized part of a slice is written.
+    let mut buf = Vec::with_capacity(16);
+    buf.extend_from_slice(b"CAT");
+    let (res, buf) = utils::complete_owned(ring, BufOp::write(fd, buf.slice(1..), 17))?;
+    assert_eq!(res?, 2);
+    assert_eq!(buf.bytes_init(), 2);
+
+    let (res, _) = utils::complete_owned(ring, BufOp::write(fd, "QUICK", 4))?;
+    assert_eq!(res?, 5);
+
+    let (res, buf) = utils::complete_owned(ring, BufOp::read(fd, Vec::with_capacity(64), 0))?;
+    assert_eq!(res?, text.len());
+    assert_eq!(buf, b"The QUICK brown fAT jumps over the lazy dog.");
+
+    // Failures hand the buffer back as well.
+    let (res, buf) =
+        utils::complete_owned(ring, BufOp::read(types::Fd(-1), Vec::with_capacity(64), 0))?;
+    assert_eq!(res.unwrap_err().raw_os_error(), Some(libc::EBADF));
+    assert!(buf.is_empty());
+
+    Ok(())
+}
+
 pub fn test_file_cur_pos<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     test: &Test,
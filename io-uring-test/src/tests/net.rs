@@ -1,6 +1,7 @@
 use crate::tests::register_buf_ring;
 use crate::utils;
 use crate::Test;
+use io_uring::send_zc::{SendZcEvent, SendZcTracker};
 use io_uring::squeue::Flags;
 use io_uring::types::{BufRingEntry, Fd};
 use io_uring::{cqueue, opcode, squeue, types, IoUring};
@@ -9,7 +10,8 @@ use std::convert::TryInto;
 use std::io::{Read, Write};
 use std::net::{Shutdown, TcpListener, TcpStream};
 use std::os::fd::FromRawFd;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
 use std::{io, mem};
 
 static TCP_LISTENER: OnceCell<TcpListener> = OnceCell::new();
@@ -246,6 +248,94 @@ pub fn test_tcp_zero_copy_send_recv<S: squeue::EntryMarker, C: cqueue::EntryMark
     Ok(())
 }
 
+pub fn test_tcp_zero_copy_send_tracker<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::SendZc::CODE);
+        test.probe.is_supported(opcode::FutexWait::CODE); // IORING_SEND_ZC_REPORT_USAGE needs 6.2
+    );
+
+    println!("test tcp_zero_copy_send_tracker");
+
+    let (send_stream, mut recv_stream) = tcp_pair()?;
+    let send_fd = types::Fd(send_stream.as_raw_fd());
+
+    let mut tracker = SendZcTracker::new();
+
+    let buf = tracker.track(0x11, b"The quick brown fox ".to_vec());
+    let send_e = opcode::SendZc::new(send_fd, buf.as_ptr(), buf.len() as _)
+        .report_usage(true)
+        .build()
+        .user_data(0x11)
+        .flags(squeue::Flags::IO_LINK);
+
+    let buf = tracker.track(0x12, b"jumps over the lazy dog.".to_vec());
+    let send2_e = opcode::SendZc::new(send_fd, buf.as_ptr(), buf.len() as _)
+        .build()
+        .user_data(0x12);
+
+    // Even a send that is rejected outright only gives up its buffer with the notification.
+    let buf = tracker.track(0x13, vec![0; 8]);
+    let bad_e = opcode::SendZc::new(send_fd, buf.as_ptr(), buf.len() as _)
+        .zc_flags(0x8000)
+        .build()
+        .user_data(0x13);
+
+    unsafe {
+        let mut queue = ring.submission();
+        queue.push(&send_e.into()).expect("queue is full");
+        queue.push(&send2_e.into()).expect("queue is full");
+        queue.push(&bad_e.into()).expect("queue is full");
+    }
+
+    assert_eq!(tracker.len(), 3);
+
+    let mut sent = Vec::new();
+    let mut released = Vec::new();
+    while !tracker.is_empty() {
+        ring.submit_and_wait(1)?;
+
+        let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+        for cqe in &cqes {
+            match tracker.complete(cqe).expect("untracked completion") {
+                SendZcEvent::Sent(result) => sent.push((cqe.user_data(), result)),
+                SendZcEvent::Released(completion) => released.push((cqe.user_data(), completion)),
+                SendZcEvent::Notified => panic!("notification before the result"),
+            }
+        }
+    }
+
+    sent.sort_unstable();
+    assert_eq!(sent, vec![(0x11, 20), (0x12, 24), (0x13, -libc::EINVAL)]);
+
+    released.sort_unstable_by_key(|(user_data, _)| *user_data);
+    assert_eq!(released.len(), 3);
+    assert_eq!(released[0].0, 0x11);
+    assert_eq!(released[0].1.result(), 20);
+    // Loopback traffic is always copied.
+    assert!(released[0].1.copied());
+    assert_eq!(released[1].0, 0x12);
+    assert_eq!(released[1].1.result(), 24);
+    assert!(!released[1].1.copied());
+    assert_eq!(released[2].0, 0x13);
+    assert_eq!(released[2].1.result(), -libc::EINVAL);
+    assert_eq!(released[2].1.buf(), &vec![0; 8]);
+
+    let mut output = Vec::new();
+    for (_, completion) in released.drain(..2) {
+        output.extend(completion.into_buf());
+    }
+    send_stream.shutdown(Shutdown::Write)?;
+    let mut received = Vec::new();
+    recv_stream.read_to_end(&mut received)?;
+    assert_eq!(received, output);
+
+    Ok(())
+}
+
 pub fn test_tcp_zero_copy_send_fixed<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     test: &Test,
@@ -821,6 +911,89 @@ pub fn test_tcp_connect<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     Ok(())
 }
 
+pub fn test_socket_addr_connect_accept<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    use socket2::{Domain, Socket, Type};
+    use std::convert::TryFrom;
+    use std::os::unix::net::UnixListener;
+
+    require!(
+        test;
+        test.probe.is_supported(opcode::Connect::CODE);
+        test.probe.is_supported(opcode::Accept::CODE);
+    );
+
+    println!("test socket_addr_connect_accept");
+
+    let mut connect_accept = |listener: RawFd, client: RawFd, addr: Pin<&types::SockAddr>| {
+        let mut peer = Box::pin(types::SockAddr::new());
+
+        let connect_e = opcode::Connect::with_addr(types::Fd(client), addr);
+        let accept_e = opcode::Accept::with_addr(types::Fd(listener), peer.as_mut());
+
+        unsafe {
+            let mut queue = ring.submission();
+            queue
+                .push(&accept_e.build().user_data(0x10).into())
+                .expect("queue is full");
+            queue
+                .push(&connect_e.build().user_data(0x11).into())
+                .expect("queue is full");
+        }
+
+        ring.submit_and_wait(2)?;
+
+        let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+        cqes.sort_unstable_by_key(cqueue::Entry::user_data);
+
+        assert_eq!(cqes.len(), 2);
+        assert_eq!(cqes[0].user_data(), 0x10);
+        assert!(cqes[0].result() >= 0);
+        assert_eq!(cqes[1].user_data(), 0x11);
+        assert_eq!(cqes[1].result(), 0);
+
+        unsafe { libc::close(cqes[0].result()) };
+
+        Ok::<_, anyhow::Error>(peer)
+    };
+
+    // TCP
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = Box::pin(types::SockAddr::from(listener.local_addr()?));
+    assert_eq!(addr.as_socket_addr(), Some(listener.local_addr()?));
+
+    let client = Socket::new(Domain::IPV4, Type::STREAM, None)?;
+    let peer = connect_accept(listener.as_raw_fd(), client.as_raw_fd(), addr.as_ref())?;
+
+    assert_eq!(peer.family(), libc::AF_INET as libc::sa_family_t);
+    assert_eq!(peer.as_socket_addr(), client.local_addr()?.as_socket());
+    assert!(peer.as_unix_addr().is_none());
+
+    // Unix domain sockets
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("socket");
+    let listener = UnixListener::bind(&path)?;
+    let addr = Box::pin(types::SockAddr::try_from(&listener.local_addr()?)?);
+    assert_eq!(
+        addr.as_unix_addr().unwrap().as_pathname(),
+        Some(path.as_path())
+    );
+
+    let client = Socket::new(Domain::UNIX, Type::STREAM, None)?;
+    let peer = connect_accept(listener.as_raw_fd(), client.as_raw_fd(), addr.as_ref())?;
+
+    // The client never bound its socket.
+    assert_eq!(peer.family(), libc::AF_UNIX as libc::sa_family_t);
+    assert!(peer.as_unix_addr().unwrap().is_unnamed());
+    assert!(peer.as_socket_addr().is_none());
+
+    Ok(())
+}
+
 pub fn test_tcp_buffer_select<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     test: &Test,
@@ -1461,6 +1634,137 @@ pub fn test_tcp_shutdown<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     Ok(())
 }
 
+pub fn test_tcp_getsockopt<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    // Socket commands were introduced in kernel 6.7, as was the opcode for FutexWait.
+    require!(
+        test;
+        test.probe.is_supported(opcode::UringCmd16::CODE);
+        test.probe.is_supported(opcode::FutexWait::CODE);
+    );
+
+    println!("test tcp_getsockopt");
+
+    let (send_stream, _recv_stream) = tcp_pair()?;
+
+    // Check for a pending error, as after a non-blocking connect.
+
+    let mut optval: libc::c_int = -1;
+    let getsockopt_e = opcode::GetSockOpt::new(
+        types::Fd(send_stream.as_raw_fd()),
+        libc::SOL_SOCKET as _,
+        libc::SO_ERROR as _,
+        &mut optval as *mut _ as *mut libc::c_void,
+        mem::size_of_val(&optval) as _,
+    );
+
+    unsafe {
+        ring.submission()
+            .push(&getsockopt_e.build().user_data(0x29).into())
+            .expect("queue is full");
+    }
+
+    ring.submit_and_wait(1)?;
+
+    let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+
+    assert_eq!(cqes.len(), 1);
+    assert_eq!(cqes[0].user_data(), 0x29);
+    assert_eq!(cqes[0].result(), mem::size_of_val(&optval) as i32);
+    assert_eq!(
+        cqes[0].decode::<opcode::GetSockOpt>()?,
+        mem::size_of_val(&optval)
+    );
+    assert_eq!(optval, 0);
+
+    // And through a fixed file.
+
+    let _ = ring.submitter().unregister_files();
+    ring.submitter()
+        .register_files(&[send_stream.as_raw_fd()])?;
+
+    let getsockopt_e = opcode::GetSockOpt::new(
+        types::Fixed(0),
+        libc::SOL_SOCKET as _,
+        libc::SO_TYPE as _,
+        &mut optval as *mut _ as *mut libc::c_void,
+        mem::size_of_val(&optval) as _,
+    );
+
+    unsafe {
+        ring.submission()
+            .push(&getsockopt_e.build().user_data(0x2a).into())
+            .expect("queue is full");
+    }
+
+    ring.submit_and_wait(1)?;
+
+    let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+
+    assert_eq!(cqes.len(), 1);
+    assert_eq!(cqes[0].user_data(), 0x2a);
+    assert_eq!(cqes[0].result(), mem::size_of_val(&optval) as i32);
+    assert_eq!(optval, libc::SOCK_STREAM);
+
+    ring.submitter().unregister_files()?;
+
+    Ok(())
+}
+
+pub fn test_tcp_siocinq_siocoutq<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    // Socket commands were introduced in kernel 6.7, as was the opcode for FutexWait.
+    require!(
+        test;
+        test.probe.is_supported(opcode::UringCmd16::CODE);
+        test.probe.is_supported(opcode::FutexWait::CODE);
+    );
+
+    println!("test tcp_siocinq_siocoutq");
+
+    let (mut send_stream, recv_stream) = tcp_pair()?;
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+    send_stream.write_all(text)?;
+
+    let _ = ring.submitter().unregister_files();
+    ring.submitter()
+        .register_files(&[recv_stream.as_raw_fd()])?;
+
+    let siocinq_e = opcode::SiocInq::new(types::Fixed(0));
+    let siocoutq_e = opcode::SiocOutQ::new(types::Fd(send_stream.as_raw_fd()));
+
+    unsafe {
+        let mut queue = ring.submission();
+        queue
+            .push(&siocinq_e.build().user_data(0x2b).into())
+            .expect("queue is full");
+        queue
+            .push(&siocoutq_e.build().user_data(0x2c).into())
+            .expect("queue is full");
+    }
+
+    ring.submit_and_wait(2)?;
+
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_by_key(|cqe| cqe.user_data());
+
+    assert_eq!(cqes.len(), 2);
+    assert_eq!(cqes[0].user_data(), 0x2b);
+    assert_eq!(cqes[0].result(), text.len() as i32);
+    // Whether the bytes are acknowledged yet depends on timing, even over loopback.
+    assert_eq!(cqes[1].user_data(), 0x2c);
+    assert!((0..=text.len() as i32).contains(&cqes[1].result()));
+
+    ring.submitter().unregister_files()?;
+
+    Ok(())
+}
+
 pub fn test_socket<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     test: &Test,
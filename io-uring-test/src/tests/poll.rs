@@ -233,3 +233,123 @@ pub fn test_eventfd_poll_multi<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
 
     Ok(())
 }
+
+pub fn test_eventfd_poll_update<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::PollAdd::CODE);
+        test.probe.is_supported(opcode::MkDirAt::CODE); // Available since 5.15 when poll update was available 5.13.
+    );
+
+    println!("test eventfd_poll_update");
+
+    let mut fd = unsafe {
+        let fd = libc::eventfd(0, libc::EFD_CLOEXEC);
+
+        if fd == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        File::from_raw_fd(fd)
+    };
+
+    // add a multi shot poll, and move it to new user data
+
+    let poll_e = opcode::PollAdd::new(types::Fd(fd.as_raw_fd()), libc::POLLIN as _).multi(true);
+    let update_e = opcode::PollUpdate::new(0x10)
+        .new_user_data(Some(0x11))
+        .build()
+        .user_data(0x12);
+
+    unsafe {
+        let mut queue = ring.submission();
+        queue
+            .push(&poll_e.build().user_data(0x10).into())
+            .expect("queue is full");
+        queue.push(&update_e.into()).expect("queue is full");
+    }
+
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.user_data(), 0x12);
+    assert_eq!(cqe.result(), 0);
+
+    fd.write_all(&0x1u64.to_ne_bytes())?;
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.user_data(), 0x11);
+    assert_eq!(cqe.result(), libc::POLLIN as _);
+    assert!(cqueue::more(cqe.flags()));
+
+    // the old user data is gone
+
+    let update_e = opcode::PollUpdate::new(0x10)
+        .new_user_data(Some(0x13))
+        .build()
+        .user_data(0x14);
+
+    unsafe {
+        ring.submission()
+            .push(&update_e.into())
+            .expect("queue is full");
+    }
+
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.user_data(), 0x14);
+    assert_eq!(cqe.result(), -libc::ENOENT);
+
+    // switch to events that are ready, which the poll reports at once
+
+    let update_e = opcode::PollUpdate::new(0x11)
+        .flags(Some(libc::POLLOUT as _))
+        .build()
+        .user_data(0x15);
+
+    unsafe {
+        ring.submission()
+            .push(&update_e.into())
+            .expect("queue is full");
+    }
+
+    ring.submit_and_wait(2)?;
+
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_by_key(|cqe| cqe.user_data());
+
+    assert_eq!(cqes.len(), 2);
+    assert_eq!(cqes[0].user_data(), 0x11);
+    assert_eq!(cqes[0].result(), libc::POLLOUT as _);
+    assert!(cqueue::more(cqes[0].flags()));
+    assert_eq!(cqes[1].user_data(), 0x15);
+    assert_eq!(cqes[1].result(), 0);
+
+    // remove poll
+
+    let remove_e = opcode::PollRemove::new(0x11).build().user_data(0x16);
+
+    unsafe {
+        ring.submission()
+            .push(&remove_e.into())
+            .expect("queue is full");
+    }
+
+    ring.submit_and_wait(2)?;
+
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_by_key(|cqe| cqe.user_data());
+
+    assert_eq!(cqes.len(), 2);
+    assert_eq!(cqes[0].user_data(), 0x11);
+    assert_eq!(cqes[0].result(), -libc::ECANCELED);
+    assert_eq!(cqes[1].user_data(), 0x16);
+    assert_eq!(cqes[1].result(), 0);
+
+    Ok(())
+}
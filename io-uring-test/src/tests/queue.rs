@@ -1,5 +1,11 @@
 use crate::Test;
-use io_uring::{cqueue, opcode, squeue, types, IoUring};
+use io_uring::chain::Chain;
+use io_uring::file_table::FixedFileTable;
+use io_uring::registry::{Completion, OpRegistry};
+use io_uring::{cqueue, opcode, register, squeue, types, IoUring};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::{io, ptr};
 
 pub fn test_nop<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
@@ -29,6 +35,177 @@ pub fn test_nop<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     Ok(())
 }
 
+pub fn test_nop_inject_result<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require! {
+        test;
+    }
+
+    println!("test nop_inject_result");
+
+    let nop_e = opcode::Nop::new().result(Some(5)).build().user_data(0x44);
+
+    unsafe {
+        ring.submission()
+            .push(&nop_e.into())
+            .expect("queue is full");
+    }
+
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.user_data(), 0x44);
+    if cqe.result() == -libc::EINVAL {
+        println!("nop options are not supported by the kernel, skip");
+        return Ok(());
+    }
+    assert_eq!(cqe.result(), 5);
+
+    // An injected error severs the link, cancelling the requests that follow.
+
+    let fail_e = opcode::Nop::new()
+        .result(Some(-libc::ECONNRESET))
+        .build()
+        .flags(squeue::Flags::IO_LINK)
+        .user_data(0x45);
+    let linked_e = opcode::Nop::new()
+        .build()
+        .flags(squeue::Flags::IO_LINK)
+        .user_data(0x46);
+    let last_e = opcode::Nop::new().build().user_data(0x47);
+
+    unsafe {
+        let mut queue = ring.submission();
+        queue.push(&fail_e.into()).expect("queue is full");
+        queue.push(&linked_e.into()).expect("queue is full");
+        queue.push(&last_e.into()).expect("queue is full");
+    }
+
+    ring.submit_and_wait(3)?;
+
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_by_key(|cqe| cqe.user_data());
+
+    assert_eq!(cqes.len(), 3);
+    assert_eq!(cqes[0].user_data(), 0x45);
+    assert_eq!(cqes[0].result(), -libc::ECONNRESET);
+    assert_eq!(cqes[1].user_data(), 0x46);
+    assert_eq!(cqes[1].result(), -libc::ECANCELED);
+    assert_eq!(cqes[2].user_data(), 0x47);
+    assert_eq!(cqes[2].result(), -libc::ECANCELED);
+
+    // A hard link carries on regardless.
+
+    let fail_e = opcode::Nop::new()
+        .result(Some(-libc::ECONNRESET))
+        .build()
+        .flags(squeue::Flags::IO_HARDLINK)
+        .user_data(0x48);
+    let last_e = opcode::Nop::new().build().user_data(0x49);
+
+    unsafe {
+        let mut queue = ring.submission();
+        queue.push(&fail_e.into()).expect("queue is full");
+        queue.push(&last_e.into()).expect("queue is full");
+    }
+
+    ring.submit_and_wait(2)?;
+
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_by_key(|cqe| cqe.user_data());
+
+    assert_eq!(cqes.len(), 2);
+    assert_eq!(cqes[0].result(), -libc::ECONNRESET);
+    assert_eq!(cqes[1].result(), 0);
+
+    Ok(())
+}
+
+pub fn test_nop_file_buffer<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require! {
+        test;
+    }
+
+    println!("test nop_file_buffer");
+
+    let file = std::fs::File::open("/dev/null")?;
+    let _ = ring.submitter().unregister_files();
+    let _ = ring.submitter().unregister_buffers();
+
+    let nops = vec![
+        // (nop, expected result)
+        (opcode::Nop::new().fd(Some(types::Fd(file.as_raw_fd()))), 0),
+        (opcode::Nop::new().fd(Some(types::Fd(-1))), -libc::EBADF),
+        (
+            opcode::Nop::new().fixed_fd(Some(types::Fixed(0))),
+            -libc::EBADF,
+        ),
+        (opcode::Nop::new().buf_index(Some(0)), -libc::EFAULT),
+        // The lookup failing overrides the injected result.
+        (
+            opcode::Nop::new().fd(Some(types::Fd(-1))).result(Some(1)),
+            -libc::EBADF,
+        ),
+    ];
+
+    for (i, (nop, expected)) in nops.into_iter().enumerate() {
+        unsafe {
+            ring.submission()
+                .push(&nop.build().user_data(i as _).into())
+                .expect("queue is full");
+        }
+
+        ring.submit_and_wait(1)?;
+
+        let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+        assert_eq!(cqe.user_data(), i as u64);
+        if i == 0 && cqe.result() == -libc::EINVAL {
+            println!("nop options are not supported by the kernel, skip");
+            return Ok(());
+        }
+        assert_eq!(cqe.result(), expected, "nop {}", i);
+    }
+
+    // And succeeds once they are registered.
+
+    let mut buf = [0u8; 16];
+    ring.submitter().register_files(&[file.as_raw_fd()])?;
+    unsafe {
+        ring.submitter().register_buffers(&[libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        }])?;
+    }
+
+    let nop_e = opcode::Nop::new()
+        .fixed_fd(Some(types::Fixed(0)))
+        .buf_index(Some(0))
+        .build()
+        .user_data(0x4a);
+
+    unsafe {
+        ring.submission()
+            .push(&nop_e.into())
+            .expect("queue is full");
+    }
+
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.user_data(), 0x4a);
+    assert_eq!(cqe.result(), 0);
+
+    ring.submitter().unregister_files()?;
+    ring.submitter().unregister_buffers()?;
+
+    Ok(())
+}
+
 pub fn test_setup_no_sqarray<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     _ring: &mut IoUring<S, C>,
     test: &Test,
@@ -66,6 +243,306 @@ pub fn test_setup_no_sqarray<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     Ok(())
 }
 
+pub fn test_setup_no_mmap<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    _ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require! {
+        test;
+    }
+
+    println!("test setup_no_mmap");
+
+    // One page for the rings and one for the submission queue entries, which is plenty for 8
+    // entries of any size.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let mem = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            page_size * 2,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_POPULATE,
+            -1,
+            0,
+        )
+    };
+    if mem == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let result = (|| -> anyhow::Result<()> {
+        let mut ring = match unsafe {
+            IoUring::<S, C>::builder()
+                .setup_no_mmap(mem, mem.cast::<u8>().add(page_size).cast())
+                .build(8)
+        } {
+            Ok(ring) => ring,
+            Err(err) => match err.raw_os_error() {
+                Some(libc::EINVAL) | Some(libc::EOPNOTSUPP) => {
+                    println!("IORING_SETUP_NO_MMAP is not supported by the kernel, skip");
+                    return Ok(());
+                }
+                _ => return Err(err.into()),
+            },
+        };
+
+        for i in 0..16 {
+            let nop_e = opcode::Nop::new().build().user_data(0x4242 + i).into();
+
+            unsafe {
+                ring.submission().push(&nop_e).expect("queue is full");
+            }
+
+            ring.submit_and_wait(1)?;
+
+            let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+            assert_eq!(cqes.len(), 1);
+            assert_eq!(cqes[0].user_data(), 0x4242 + i);
+            assert_eq!(cqes[0].result(), 0);
+        }
+
+        assert!(matches!(
+            ring.resize(16, 32),
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL)
+        ));
+
+        Ok(())
+    })();
+
+    unsafe {
+        libc::munmap(mem, page_size * 2);
+    }
+
+    result
+}
+
+pub fn test_setup_registered_fd_only<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    _ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require! {
+        test;
+    }
+
+    println!("test setup_registered_fd_only");
+
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let mem = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            page_size * 2,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_POPULATE,
+            -1,
+            0,
+        )
+    };
+    if mem == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let count_fds = || std::fs::read_dir("/proc/self/fd").map(Iterator::count);
+
+    let result = (|| -> anyhow::Result<()> {
+        let fds = count_fds()?;
+
+        let mut ring = match unsafe {
+            IoUring::<S, C>::builder()
+                .setup_no_mmap(mem, mem.cast::<u8>().add(page_size).cast())
+                .setup_registered_fd_only()
+                .build(8)
+        } {
+            Ok(ring) => ring,
+            Err(err) => match err.raw_os_error() {
+                Some(libc::EINVAL) | Some(libc::EOPNOTSUPP) => {
+                    println!(
+                        "IORING_SETUP_REGISTERED_FD_ONLY is not supported by the kernel, skip"
+                    );
+                    return Ok(());
+                }
+                _ => return Err(err.into()),
+            },
+        };
+
+        // The ring never shows up in the file descriptor table.
+
+        assert!(ring.params().is_setup_registered_fd_only());
+        assert_eq!(ring.try_as_raw_fd(), None);
+        assert_eq!(count_fds()?, fds);
+
+        for i in 0..4 {
+            let nop_e = opcode::Nop::new().build().user_data(0x4242 + i).into();
+
+            unsafe {
+                ring.submission().push(&nop_e).expect("queue is full");
+            }
+
+            ring.submit_and_wait(1)?;
+
+            let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+            assert_eq!(cqes.len(), 1);
+            assert_eq!(cqes[0].user_data(), 0x4242 + i);
+            assert_eq!(cqes[0].result(), 0);
+        }
+
+        // Registering works through the registered index, but it cannot be given up.
+
+        let mut submitter = ring.submitter();
+        submitter.register_files_sparse(4)?;
+        submitter.unregister_files()?;
+
+        assert_eq!(
+            submitter.register_ring_fd().unwrap_err().raw_os_error(),
+            Some(libc::EEXIST)
+        );
+        assert_eq!(
+            submitter.unregister_ring_fd().unwrap_err().raw_os_error(),
+            Some(libc::EINVAL)
+        );
+        assert_eq!(
+            FixedFileTable::new(&submitter, 4)
+                .unwrap_err()
+                .raw_os_error(),
+            Some(libc::EBADF)
+        );
+
+        Ok(())
+    })();
+
+    unsafe {
+        libc::munmap(mem, page_size * 2);
+    }
+
+    result
+}
+
+pub fn test_setup_hybrid_iopoll<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    _ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require! {
+        test;
+    }
+
+    println!("test setup_hybrid_iopoll");
+
+    let mut ring = match IoUring::<S, C>::builder().setup_hybrid_iopoll().build(8) {
+        Ok(ring) => ring,
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+            println!("IORING_SETUP_HYBRID_IOPOLL is not supported by the kernel, skip");
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    assert!(ring.params().is_setup_iopoll());
+    assert!(ring.params().is_setup_hybrid_iopoll());
+
+    let nop_e = opcode::Nop::new().build().user_data(0x42).into();
+
+    unsafe {
+        ring.submission().push(&nop_e).expect("queue is full");
+    }
+
+    ring.submit_and_wait(1)?;
+
+    let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    assert_eq!(cqes.len(), 1);
+    assert_eq!(cqes[0].user_data(), 0x42);
+    assert_eq!(cqes[0].result(), 0);
+
+    Ok(())
+}
+
+pub fn test_resize_rings<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    _ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require! {
+        test;
+    }
+
+    println!("test resize_rings");
+
+    let mut ring = match IoUring::<S, C>::builder()
+        .setup_single_issuer()
+        .setup_defer_taskrun()
+        .setup_no_sqarray()
+        .build(8)
+    {
+        Ok(ring) => ring,
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+            println!("IORING_SETUP_NO_SQARRAY is not supported by the kernel, skip");
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    // Rings with a submission queue array are refused, as the kernel does not report where the
+    // array of the new rings lives.
+    if let Ok(mut ring) = IoUring::<S, C>::builder()
+        .setup_single_issuer()
+        .setup_defer_taskrun()
+        .build(8)
+    {
+        assert!(matches!(
+            ring.resize(16, 64),
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL)
+        ));
+        assert_eq!(ring.submission().capacity(), 8);
+    }
+
+    // Registered resources must survive the resize.
+    ring.submitter().register_files_sparse(4)?;
+
+    // Leave a completion pending, it must be carried over to the new ring.
+    let nop_e = opcode::Nop::new().build().user_data(0x42).into();
+    unsafe {
+        ring.submission().push(&nop_e).expect("queue is full");
+    }
+    ring.submit_and_wait(1)?;
+
+    match ring.resize(16, 64) {
+        Ok(()) => {}
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+            println!("IORING_REGISTER_RESIZE_RINGS is not supported by the kernel, skip");
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    assert_eq!(ring.params().sq_entries(), 16);
+    assert_eq!(ring.params().cq_entries(), 64);
+    assert_eq!(ring.submission().capacity(), 16);
+    assert_eq!(ring.completion().capacity(), 64);
+
+    let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    assert_eq!(cqes.len(), 1);
+    assert_eq!(cqes[0].user_data(), 0x42);
+    assert_eq!(cqes[0].result(), 0);
+
+    // The whole of the new submission queue is usable.
+    unsafe {
+        let sqes: Vec<S> = (0..16)
+            .map(|i| opcode::Nop::new().build().user_data(0x100 + i).into())
+            .collect();
+        ring.submission()
+            .push_multiple(&sqes)
+            .expect("queue is full");
+    }
+    ring.submit_and_wait(16)?;
+
+    let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    assert_eq!(cqes.len(), 16);
+    let mut user_data: Vec<u64> = cqes.iter().map(|cqe| cqe.user_data()).collect();
+    user_data.sort_unstable();
+    assert_eq!(user_data, (0x100..0x110).collect::<Vec<u64>>());
+
+    ring.submitter().unregister_files()?;
+
+    Ok(())
+}
+
 pub fn test_batch<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     test: &Test,
@@ -189,6 +666,195 @@ pub fn test_debug_print<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     Ok(())
 }
 
+pub fn test_op_registry<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::Read::CODE);
+        test.probe.is_supported(opcode::PollAdd::CODE);
+        test.probe.is_supported(opcode::AsyncCancel::CODE);
+        test.probe.is_supported(opcode::MkDirAt::CODE); // Available since 5.15, multishot poll since 5.13.
+    );
+
+    println!("test op_registry");
+
+    #[derive(Debug)]
+    enum Op {
+        Read(Vec<u8>),
+        Poll,
+        Cancel,
+    }
+
+    let (rx, mut tx) = io::pipe()?;
+    let mut registry = OpRegistry::new();
+
+    let mut buf = vec![0; 16];
+    let read_e = opcode::Read::new(types::Fd(rx.as_raw_fd()), buf.as_mut_ptr(), 16).build();
+    let read_ud = unsafe { registry.push(&mut ring.submission(), read_e.into(), Op::Read(buf)) }
+        .expect("queue is full");
+
+    let poll_e = opcode::PollAdd::new(types::Fd(tx.as_raw_fd()), libc::POLLOUT as _)
+        .multi(true)
+        .build();
+    let poll_ud = unsafe { registry.push(&mut ring.submission(), poll_e.into(), Op::Poll) }
+        .expect("queue is full");
+
+    assert_ne!(read_ud, poll_ud);
+    assert_eq!(registry.len(), 2);
+
+    // The pipe is writable, so the poll completes right away and stays armed.
+    ring.submit_and_wait(1)?;
+    let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+
+    assert_eq!(cqes.len(), 1);
+    assert_eq!(cqes[0].user_data(), poll_ud);
+    assert!(matches!(
+        registry.complete(&cqes[0]),
+        Some(Completion::More(Op::Poll))
+    ));
+    assert!(registry.contains(poll_ud));
+
+    tx.write_all(b"hello")?;
+
+    ring.submit_and_wait(1)?;
+    let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+
+    assert_eq!(cqes.len(), 1);
+    assert_eq!(cqes[0].user_data(), read_ud);
+    assert_eq!(cqes[0].result(), 5);
+    match registry.complete(&cqes[0]) {
+        Some(Completion::Done(Op::Read(buf))) => assert_eq!(&buf[..5], b"hello"),
+        other => panic!("unexpected completion: {:?}", other),
+    }
+    assert!(!registry.contains(read_ud));
+    assert!(registry.get(read_ud).is_none());
+
+    // The slot of the read is reused under a different user_data.
+    let cancel_e = opcode::AsyncCancel::new(poll_ud).build();
+    let cancel_ud = unsafe { registry.push(&mut ring.submission(), cancel_e.into(), Op::Cancel) }
+        .expect("queue is full");
+    assert_ne!(cancel_ud, read_ud);
+    assert_eq!(cancel_ud as u32, read_ud as u32);
+
+    ring.submit_and_wait(2)?;
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_by_key(|cqe| cqe.user_data());
+
+    assert_eq!(cqes.len(), 2);
+    for cqe in &cqes {
+        match registry.complete(cqe) {
+            Some(Completion::Done(Op::Cancel)) => assert_eq!(cqe.result(), 0),
+            Some(Completion::Done(Op::Poll)) => assert_eq!(cqe.result(), -libc::ECANCELED),
+            other => panic!("unexpected completion: {:?}", other),
+        }
+    }
+    assert!(registry.is_empty());
+
+    // A completion for a stale user_data is not claimed.
+    assert!(registry.complete(&cqes[0]).is_none());
+
+    Ok(())
+}
+
+pub fn test_chain_link_timeout<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::Read::CODE);
+        test.probe.is_supported(opcode::LinkTimeout::CODE);
+    );
+
+    println!("test chain_link_timeout");
+
+    let (rx, _tx) = io::pipe()?;
+    let mut buf = vec![0; 16];
+    let ts = types::Timespec::new().nsec(10_000_000);
+
+    // The read never completes, so it is cancelled by its timeout. The link to the first nop is
+    // hard and survives, the link to the last one is severed by the failing read.
+    let chain = Chain::new(opcode::Nop::new().build())
+        .then(opcode::Read::new(types::Fd(rx.as_raw_fd()), buf.as_mut_ptr(), 16).build())
+        .link_timeout(opcode::LinkTimeout::new(&ts))
+        .then_hard(opcode::Nop::new().build())
+        .then(opcode::Read::new(types::Fd(-1), buf.as_mut_ptr(), 16).build())
+        .then(opcode::Nop::new().build());
+    assert_eq!(chain.len(), 6);
+
+    let mut completions =
+        unsafe { chain.push(&mut ring.submission(), 0x200) }.expect("queue is full");
+
+    ring.submit_and_wait(6)?;
+
+    let mut steps = Vec::new();
+    for cqe in ring.completion().map(Into::<cqueue::Entry>::into) {
+        steps.push(
+            completions
+                .complete(&cqe)
+                .expect("completion outside the chain"),
+        );
+    }
+    steps.sort_by_key(|step| step.position());
+
+    assert!(completions.is_done());
+    assert_eq!(
+        completions.results(),
+        &[
+            Some(0),
+            Some(-libc::ECANCELED),
+            Some(-libc::ETIME),
+            Some(0),
+            Some(-libc::EBADF),
+            Some(-libc::ECANCELED),
+        ]
+    );
+    let cancelled: Vec<_> = steps.iter().map(|step| step.is_cancelled()).collect();
+    assert_eq!(cancelled, [false, true, false, false, false, true]);
+    assert!(steps[2].is_link_timeout());
+
+    // A completion that is not part of the chain is not claimed.
+    let nop_e = opcode::Nop::new().build().user_data(0x200 + 6);
+    unsafe {
+        ring.submission()
+            .push(&nop_e.into())
+            .expect("queue is full");
+    }
+    ring.submit_and_wait(1)?;
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert!(completions.complete(&cqe).is_none());
+
+    Ok(())
+}
+
+pub fn test_chain_push_all_or_nothing<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+    );
+
+    println!("test chain_push_all_or_nothing");
+
+    let mut sq = ring.submission();
+    let room = sq.capacity() - sq.len();
+    assert!(room >= 2);
+
+    let mut chain = Chain::new(opcode::Nop::new().build());
+    for _ in 0..room {
+        chain = chain.then(opcode::Nop::new().build());
+    }
+
+    // One entry too many, so nothing is pushed.
+    assert!(unsafe { chain.push(&mut sq, 0x300) }.is_err());
+    assert_eq!(sq.capacity() - sq.len(), room);
+
+    Ok(())
+}
+
 pub fn test_msg_ring_data<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     test: &Test,
@@ -342,6 +1008,63 @@ pub fn test_msg_ring_send_fd<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     Ok(())
 }
 
+pub fn test_send_msg_ring<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    _ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+    );
+
+    println!("test send_msg_ring");
+
+    let mut dest_ring = IoUring::<S, C>::builder().build(4)?;
+    let fd = dest_ring.as_raw_fd();
+
+    match register::send_msg_ring(fd, 82, 85, None) {
+        Ok(()) => (),
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+            println!("IORING_REGISTER_SEND_MSG_RING is not supported by the kernel, skip");
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    let cqes: Vec<cqueue::Entry> = dest_ring.completion().map(Into::into).collect();
+    assert_eq!(cqes.len(), 1);
+    assert_eq!(cqes[0].user_data(), 85);
+    assert_eq!(cqes[0].result(), 82);
+    assert_eq!(cqes[0].flags(), 0);
+
+    // Wake up the ring from a thread that has no ring of its own.
+
+    let sender = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        register::send_msg_ring(fd, -1, 0x42, Some(0x1234))
+    });
+
+    dest_ring.submit_and_wait(1)?;
+    sender.join().unwrap()?;
+
+    let cqes: Vec<cqueue::Entry> = dest_ring.completion().map(Into::into).collect();
+    assert_eq!(cqes.len(), 1);
+    assert_eq!(cqes[0].user_data(), 0x42);
+    assert_eq!(cqes[0].result(), -1);
+    assert_eq!(cqes[0].flags(), 0x1234);
+
+    // The target must be a ring.
+
+    let file = tempfile::tempfile()?;
+    assert_eq!(
+        register::send_msg_ring(file.as_raw_fd(), 0, 0, None)
+            .unwrap_err()
+            .raw_os_error(),
+        Some(libc::EBADFD)
+    );
+
+    Ok(())
+}
+
 pub fn test_completion_status<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     test: &Test,
@@ -1,5 +1,8 @@
 use crate::Test;
-use io_uring::{cqueue, opcode, squeue, IoUring};
+use io_uring::file_table::FixedFileTable;
+use io_uring::{cqueue, opcode, squeue, types, IoUring};
+use std::io::Read;
+use std::os::unix::io::{AsRawFd, FromRawFd};
 
 pub fn test_register_files_sparse<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
@@ -69,6 +72,251 @@ pub fn test_register_files_sparse<S: squeue::EntryMarker, C: cqueue::EntryMarker
     Ok(())
 }
 
+pub fn test_register_file_alloc_range<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    // IORING_REGISTER_FILE_ALLOC_RANGE was introduced in kernel 6.0, as was the opcode for
+    // SendZc. So require the SendZc to avoid running this test on earlier kernels.
+    require!(
+        test;
+        test.probe.is_supported(opcode::SendZc::CODE);
+        test.probe.is_supported(opcode::Socket::CODE);
+    );
+
+    println!("test register_file_alloc_range");
+
+    ring.submitter().register_files_sparse(8)?;
+    ring.submitter().register_file_alloc_range(4, 2)?;
+
+    // Allocate direct descriptors until the range is exhausted: the kernel must only hand out
+    // slots 4 and 5, then fail with ENFILE.
+
+    for _ in 0..3 {
+        let socket_e = opcode::Socket::new(libc::AF_INET, libc::SOCK_DGRAM, 0)
+            .file_index(Some(types::DestinationSlot::auto_target()))
+            .build()
+            .user_data(0x61)
+            .into();
+
+        unsafe {
+            ring.submission().push(&socket_e).expect("queue is full");
+        }
+    }
+
+    ring.submit_and_wait(3)?;
+
+    let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    let mut results: Vec<i32> = cqes.iter().map(|cqe| cqe.result()).collect();
+    results.sort_unstable();
+    assert_eq!(results, [-libc::ENFILE, 4, 5]);
+
+    // A range outside of the table is rejected.
+
+    match ring.submitter().register_file_alloc_range(6, 4) {
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {}
+        Ok(()) => {
+            return Err(anyhow::anyhow!(
+                "register_file_alloc_range should not accept a range past the table"
+            ));
+        }
+        Err(e) => {
+            return Err(anyhow::anyhow!(
+                "register_file_alloc_range should have failed with EINVAL: {}",
+                e
+            ));
+        }
+    }
+
+    ring.submitter().unregister_files()?;
+
+    Ok(())
+}
+
+pub fn test_register_files_tags<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        ring.params().is_feature_resource_tagging();
+    );
+
+    println!("test register_files_tags");
+
+    // Reserve the top bit of the user data for resource tags.
+    const TAG: u64 = 1 << 63;
+
+    let first = std::fs::File::open("/dev/null")?;
+    let second = std::fs::File::open("/dev/null")?;
+    let third = std::fs::File::open("/dev/null")?;
+
+    ring.submitter().register_files_tags(
+        &[first.as_raw_fd(), second.as_raw_fd()],
+        &[TAG | 1, TAG | 2],
+    )?;
+
+    // A request completion is never taken for a tag.
+
+    let nop_e = opcode::Nop::new().build().user_data(0x62).into();
+    unsafe {
+        ring.submission().push(&nop_e).expect("queue is full");
+    }
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.user_data(), 0x62);
+    assert_eq!(cqe.resource_tag(TAG), None);
+
+    // Replacing slot 0 posts the tag of the file that was there.
+
+    let updated =
+        ring.submitter()
+            .register_files_update_tag(0, &[third.as_raw_fd()], &[TAG | 3])?;
+    assert_eq!(updated, 1);
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.resource_tag(TAG), Some(TAG | 1));
+
+    // Unregistering the table posts the tags of the remaining files.
+
+    ring.submitter().unregister_files()?;
+    ring.submit_and_wait(2)?;
+
+    let mut tags: Vec<u64> = ring
+        .completion()
+        .map(Into::<cqueue::Entry>::into)
+        .filter_map(|cqe| cqe.resource_tag(TAG))
+        .collect();
+    tags.sort_unstable();
+    assert_eq!(tags, [TAG | 2, TAG | 3]);
+
+    // Sparse entries cannot carry a tag.
+
+    match ring.submitter().register_files_tags(&[-1], &[TAG | 4]) {
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {}
+        Ok(()) => {
+            return Err(anyhow::anyhow!(
+                "register_files_tags should not accept a tagged sparse entry"
+            ));
+        }
+        Err(e) => {
+            return Err(anyhow::anyhow!(
+                "register_files_tags should have failed with EINVAL: {}",
+                e
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn test_fixed_file_table<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    // The table is built on register_files_sparse, introduced in kernel 5.19 as was the opcode
+    // for UringCmd16.
+    require!(
+        test;
+        test.probe.is_supported(opcode::UringCmd16::CODE);
+        test.probe.is_supported(opcode::Socket::CODE);
+    );
+
+    println!("test fixed_file_table");
+
+    let table = FixedFileTable::new(&ring.submitter(), 2)?;
+    assert_eq!(table.capacity(), 2);
+
+    let mut fds = [-1, -1];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let (mut rx, tx) = unsafe {
+        (
+            std::fs::File::from_raw_fd(fds[0]),
+            std::fs::File::from_raw_fd(fds[1]),
+        )
+    };
+
+    // The kernel keeps its own reference, so the write end can be closed right away.
+
+    let fixed_tx = table.insert(tx.as_raw_fd())?;
+    assert_eq!(fixed_tx.slot(), 0);
+    drop(tx);
+
+    let sock = table.reserve()?;
+    assert_eq!(sock.slot(), 1);
+    assert_eq!(table.available(), 0);
+    match table.reserve() {
+        Err(e) if e.raw_os_error() == Some(libc::ENFILE) => {}
+        other => {
+            return Err(anyhow::anyhow!(
+                "reserve should fail with ENFILE: {:?}",
+                other
+            ))
+        }
+    }
+
+    let text = b"fixed file table";
+    let write_e = opcode::Write::new(&fixed_tx, text.as_ptr(), text.len() as _)
+        .build()
+        .user_data(0x63)
+        .into();
+    let socket_e = opcode::Socket::new(libc::AF_INET, libc::SOCK_DGRAM, 0)
+        .file_index(Some(sock.dest()))
+        .build()
+        .user_data(0x64)
+        .into();
+
+    unsafe {
+        let mut queue = ring.submission();
+        queue.push(&write_e).expect("queue is full");
+        queue.push(&socket_e).expect("queue is full");
+    }
+
+    ring.submit_and_wait(2)?;
+
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_by_key(|cqe| cqe.user_data());
+    assert_eq!(cqes[0].result(), text.len() as i32);
+    assert_eq!(cqes[1].result(), 0);
+
+    let mut buf = [0; 16];
+    rx.read_exact(&mut buf)?;
+    assert_eq!(&buf, text);
+
+    // Closing the socket hands its slot back once the close has completed.
+
+    let closing = sock.close();
+    unsafe {
+        ring.submission()
+            .push(&closing.entry().user_data(0x65).into())
+            .expect("queue is full");
+    }
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.user_data(), 0x65);
+    assert_eq!(cqe.result(), 0);
+    assert_eq!(table.available(), 0);
+    closing.complete(cqe.result());
+    assert_eq!(table.available(), 1);
+
+    // Dropping the write end removes it from the table, which closes the pipe.
+
+    drop(fixed_tx);
+    assert_eq!(table.available(), 2);
+    assert_eq!(rx.read(&mut buf)?, 0);
+
+    // Dropping the table unregisters it.
+
+    drop(table);
+    ring.submitter().register_files_sparse(1)?;
+    ring.submitter().unregister_files()?;
+
+    Ok(())
+}
+
 pub fn test_register_ring_fd<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     test: &Test,
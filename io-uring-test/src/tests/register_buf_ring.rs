@@ -1,15 +1,19 @@
 // Create tests for registering buf_rings and for the buf_ring entries.
 // The entry point in this file can be found by searching for 'pub'.
 
+use crate::utils;
 use crate::Test;
+use io_uring::buf::{BufOp, SelectOp};
+use io_uring::buf_ring::{self, BufRing};
 use io_uring::types;
 use io_uring::types::BufRingEntry;
 use io_uring::{cqueue, opcode, squeue, IoUring};
 
 use std::cell::Cell;
 use std::fmt;
-use std::io;
-use std::os::unix::io::AsRawFd;
+use std::io::{self, Write};
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::ptr;
 use std::rc::Rc;
 use std::sync::atomic::{self, AtomicU16};
@@ -735,3 +739,297 @@ pub fn test_register_buf_ring<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
 
     Ok(())
 }
+
+// Read from file descriptor with buffer select, returning the result and flags of the completion.
+fn buf_select_read<S, C>(
+    ring: &mut IoUring<S, C>,
+    bgid: Bgid,
+    fd: types::Fd,
+    len: u32,
+) -> io::Result<(u32, u32)>
+where
+    S: squeue::EntryMarker,
+    C: cqueue::EntryMarker,
+{
+    let read_e = opcode::Read::new(fd, std::ptr::null_mut(), len)
+        .offset(0)
+        .buf_group(bgid);
+
+    unsafe {
+        let mut queue = ring.submission();
+        queue
+            .push(
+                &read_e
+                    .build()
+                    .user_data(0x03)
+                    .flags(squeue::Flags::BUFFER_SELECT)
+                    .into(),
+            )
+            .expect("queue is full");
+    }
+    assert_eq!(ring.submit_and_wait(1)?, 1);
+
+    let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    assert_eq!(cqes.len(), 1);
+    assert_eq!(cqes[0].user_data(), 0x03);
+
+    let result = cqes[0].result();
+    if result < 0 {
+        return Err(io::Error::from_raw_os_error(-result));
+    }
+
+    Ok((result as u32, cqes[0].flags()))
+}
+
+pub fn test_buf_ring<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> io::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::UringCmd16::CODE);
+    );
+
+    println!("test buf_ring");
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+    let len = text.len() as u32;
+
+    let buf_ring = unsafe {
+        BufRing::builder(889)
+            .ring_entries(2)
+            .buf_len(128)
+            .build(&ring.submitter())
+    }?;
+
+    let fd = tempfile::tempfile()?;
+    let fd = types::Fd(fd.as_raw_fd());
+    write_text_to_file(ring, fd, text)?;
+
+    let read = |ring: &mut IoUring<S, C>| -> io::Result<buf_ring::Buffer> {
+        let (res, flags) = buf_select_read(ring, buf_ring.bgid(), fd, len)?;
+        Ok(unsafe { buf_ring.get(res, flags) }.expect("no buffer selected"))
+    };
+
+    let buf0 = read(ring)?;
+    let buf1 = read(ring)?;
+    assert_eq!((buf0.bid(), &buf0[..]), (0, &text[..]));
+    assert_eq!((buf1.bid(), &buf1[..]), (1, &text[..]));
+
+    // The ring is empty until a buffer is dropped.
+    let err = read(ring).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::ENOBUFS));
+
+    drop(buf1);
+    drop(buf0);
+
+    let buf2 = read(ring)?;
+    let buf3 = read(ring)?;
+    assert_eq!(buf2.bid(), 1);
+    assert_eq!(buf3.bid(), 0);
+
+    // A forgotten buffer stays out of the ring until it is added back.
+    let bid = buf2.forget();
+    drop(buf3);
+    let buf4 = read(ring)?;
+    assert_eq!(buf4.bid(), 0);
+    let err = read(ring).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::ENOBUFS));
+
+    unsafe { buf_ring.add(bid) };
+    buf_ring.advance();
+    assert_eq!(read(ring)?.bid(), 1);
+    drop(buf4);
+
+    // Wrap the tail around.
+    for _ in 0..=u16::MAX {
+        read(ring)?;
+    }
+
+    drop(buf_ring);
+
+    // Dropping the ring unregisters its buffer group, so the same id can be used again.
+    let buf_ring = unsafe { BufRing::builder(889).build(&ring.submitter()) }?;
+    drop(buf_ring);
+
+    Ok(())
+}
+
+pub fn test_buf_ring_select_op<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::UringCmd16::CODE);
+    );
+
+    println!("test buf_ring_select_op");
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+
+    let buf_ring = unsafe {
+        BufRing::builder(892)
+            .ring_entries(2)
+            .buf_len(32)
+            .build(&ring.submitter())
+    }?;
+
+    let mut src = tempfile::tempfile()?;
+    src.write_all(text)?;
+    let dst = tempfile::tempfile()?;
+
+    let read = SelectOp::read(types::Fd(src.as_raw_fd()), &buf_ring, 32, 4);
+    let buf = utils::complete_owned(ring, read)??.expect("no buffer selected");
+    assert_eq!(&buf[..], &text[4..36]);
+
+    // The selected buffer is written out as is, and goes back to the ring afterwards.
+    let bid = buf.bid();
+    let (res, buf) = utils::complete_owned(ring, BufOp::write(types::Fd(dst.as_raw_fd()), buf, 0))?;
+    assert_eq!(res?, 32);
+    assert_eq!(buf.bid(), bid);
+    drop(buf);
+
+    let mut output = vec![0; 32];
+    assert_eq!(dst.read_at(&mut output, 0)?, 32);
+    assert_eq!(&output[..], &text[4..36]);
+
+    // Nothing left to read at the end of the file.
+    let read = SelectOp::read(types::Fd(src.as_raw_fd()), &buf_ring, 32, text.len() as u64);
+    let buf = utils::complete_owned(ring, read)??;
+    if let Some(buf) = buf {
+        assert!(buf.is_empty());
+    }
+
+    Ok(())
+}
+
+pub fn test_buf_ring_incremental<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> io::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::UringCmd16::CODE);
+    );
+
+    println!("test buf_ring_incremental");
+
+    let buf_ring = match unsafe {
+        BufRing::builder(890)
+            .ring_entries(1)
+            .buf_len(64)
+            .incremental(true)
+            .build(&ring.submitter())
+    } {
+        Ok(buf_ring) => buf_ring,
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+            println!("IOU_PBUF_RING_INC is not supported by the kernel, skip");
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+
+    let (rx, tx) = {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        unsafe {
+            (
+                std::fs::File::from_raw_fd(fds[0]),
+                std::fs::File::from_raw_fd(fds[1]),
+            )
+        }
+    };
+    let mut tx = tx;
+    let rx_fd = types::Fd(rx.as_raw_fd());
+
+    // Each read only consumes part of the single buffer.
+    tx.write_all(b"hello")?;
+    let (res, flags) = buf_select_read(ring, buf_ring.bgid(), rx_fd, 64)?;
+    let first = unsafe { buf_ring.get(res, flags) }.unwrap();
+    assert!(first.is_partial());
+    assert_eq!(&first[..], b"hello");
+
+    tx.write_all(b"world")?;
+    let (res, flags) = buf_select_read(ring, buf_ring.bgid(), rx_fd, 64)?;
+    let second = unsafe { buf_ring.get(res, flags) }.unwrap();
+    assert!(second.is_partial());
+    assert_eq!(second.bid(), first.bid());
+    assert_eq!(&second[..], b"world");
+
+    // Dropping partially consumed buffers doesn't hand them back.
+    drop(first);
+    drop(second);
+
+    // Fill the rest of the buffer, which the kernel is then done with.
+    tx.write_all(&[b'x'; 54])?;
+    let (res, flags) = buf_select_read(ring, buf_ring.bgid(), rx_fd, 64)?;
+    let last = unsafe { buf_ring.get(res, flags) }.unwrap();
+    assert!(!last.is_partial());
+    assert_eq!(&last[..], &[b'x'; 54][..]);
+
+    tx.write_all(b"again")?;
+    let err = buf_select_read(ring, buf_ring.bgid(), rx_fd, 64).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::ENOBUFS));
+
+    // Once the last part is dropped the whole buffer is available again.
+    drop(last);
+    let (res, flags) = buf_select_read(ring, buf_ring.bgid(), rx_fd, 64)?;
+    let buf = unsafe { buf_ring.get(res, flags) }.unwrap();
+    assert!(buf.is_partial());
+    assert_eq!(&buf[..], b"again");
+
+    Ok(())
+}
+
+pub fn test_buf_ring_head<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> io::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::UringCmd16::CODE);
+    );
+
+    println!("test buf_ring_head");
+
+    let buf_ring = unsafe {
+        BufRing::builder(891)
+            .ring_entries(4)
+            .buf_len(128)
+            .build(&ring.submitter())
+    }?;
+
+    match ring.submitter().buf_ring_head(buf_ring.bgid()) {
+        Ok(head) => assert_eq!(head, 0),
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+            println!("IORING_REGISTER_PBUF_STATUS is not supported by the kernel, skip");
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    }
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+    let fd = tempfile::tempfile()?;
+    let fd = types::Fd(fd.as_raw_fd());
+    write_text_to_file(ring, fd, text)?;
+
+    // The head moves as the kernel picks buffers, not as they are handed back.
+    let (res, flags) = buf_select_read(ring, buf_ring.bgid(), fd, text.len() as _)?;
+    let buf0 = unsafe { buf_ring.get(res, flags) }.unwrap();
+    assert_eq!(ring.submitter().buf_ring_head(buf_ring.bgid())?, 1);
+
+    let (res, flags) = buf_select_read(ring, buf_ring.bgid(), fd, text.len() as _)?;
+    let buf1 = unsafe { buf_ring.get(res, flags) }.unwrap();
+    assert_eq!(ring.submitter().buf_ring_head(buf_ring.bgid())?, 2);
+
+    drop(buf0);
+    drop(buf1);
+    assert_eq!(ring.submitter().buf_ring_head(buf_ring.bgid())?, 2);
+
+    // Querying a group that isn't registered fails.
+    let err = ring.submitter().buf_ring_head(892).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
+
+    Ok(())
+}
@@ -1,4 +1,6 @@
+use crate::utils;
 use crate::Test;
+use io_uring::buf::{BufOp, IoBuf, OwnedOp};
 use io_uring::{
     cqueue,
     opcode::{ReadFixed, WriteFixed},
@@ -10,12 +12,13 @@ use libc::iovec;
 use std::{
     fs::File,
     io::{self, IoSliceMut},
-    io::{Error, Write},
+    io::{Error, Read, Write},
     ops::DerefMut,
     os::fd::AsRawFd,
     os::fd::FromRawFd,
 };
 
+use io_uring::registered_buffers::RegisteredBuffers;
 use io_uring::{opcode, types};
 use libc::EFAULT;
 
@@ -321,7 +324,191 @@ pub fn test_register_buffers_update<S: squeue::EntryMarker, C: cqueue::EntryMark
     return Ok(());
 }
 
+pub fn test_registered_buffers<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(opcode::ReadFixed::CODE);
+        ring.params().is_feature_resource_tagging();
+    );
+
+    println!("test registered_buffers");
+
+    let (read, mut write) = create_pipe()?;
+
+    let pool = RegisteredBuffers::builder()
+        .buf_cnt(4)
+        .buf_len(100)
+        .build(&ring.submitter())?;
+
+    let mut src = pool.get(1).unwrap();
+    assert!(pool.get(1).is_none());
+    assert_eq!(src.buf_index(), 1);
+    assert_eq!(src.len(), 100);
+
+    let mut dst = pool.checkout().unwrap();
+    assert_eq!(dst.buf_index(), 0);
+
+    src[..5].copy_from_slice(b"hello");
+
+    let write_e = src
+        .write_fixed(Fd(write.as_raw_fd()), 0..5)
+        .build()
+        .user_data(0x66)
+        .into();
+    let read_e = dst
+        .read_fixed(Fd(read.as_raw_fd()), 10..20)
+        .build()
+        .user_data(0x67)
+        .into();
+
+    unsafe {
+        let mut queue = ring.submission();
+        queue.push(&write_e).expect("queue is full");
+        queue.push(&read_e).expect("queue is full");
+    }
+
+    ring.submit_and_wait(2)?;
+
+    let mut cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+    cqes.sort_by_key(|cqe| cqe.user_data());
+    assert_eq!(cqes[0].result(), 5);
+    assert_eq!(cqes[1].result(), 5);
+    assert_eq!(&dst[10..15], b"hello");
+
+    // Vectored fixed-buffer requests, if the kernel has them.
+
+    if test.probe.is_supported(opcode::WritevFixed::CODE) {
+        let iovecs = [src.iovec(0..2), src.iovec(3..5)];
+        let writev_e = src
+            .writev_fixed(Fd(write.as_raw_fd()), &iovecs)
+            .build()
+            .user_data(0x68)
+            .into();
+
+        unsafe {
+            ring.submission().push(&writev_e).expect("queue is full");
+        }
+        ring.submit_and_wait(1)?;
+
+        let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+        assert_eq!(cqe.result(), 4);
+
+        let mut buf = [0; 4];
+        (&read).read_exact(&mut buf)?;
+        assert_eq!(&buf, b"helo");
+    }
+
+    // The pool is unregistered once the last buffer is dropped.
+
+    drop(pool);
+    drop(src);
+    drop(dst);
+    match ring.submitter().unregister_buffers() {
+        Err(e) if e.raw_os_error() == Some(libc::ENXIO) => {}
+        other => {
+            return Err(anyhow::anyhow!(
+                "buffers should have been unregistered: {:?}",
+                other
+            ))
+        }
+    }
+
+    // A pool installed into part of a sparse table.
+
+    ring.submitter().register_buffers_sparse(8)?;
+    let pool = RegisteredBuffers::builder()
+        .buf_cnt(2)
+        .offset(6)
+        .build(&ring.submitter())?;
+
+    let mut dst = pool.get(1).unwrap();
+    assert_eq!(dst.buf_index(), 7);
+
+    write.write_all(b"yo")?;
+    let read_e = dst
+        .read_fixed(Fd(read.as_raw_fd()), 0..2)
+        .build()
+        .user_data(0x69)
+        .into();
+
+    unsafe {
+        ring.submission().push(&read_e).expect("queue is full");
+    }
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.result(), 2);
+    assert_eq!(&dst[..2], b"yo");
+
+    // Dropping the pool empties its slots but leaves the table registered.
+
+    let mut buf = [0; 2];
+    let read_e = ReadFixed::new(Fd(read.as_raw_fd()), buf.as_mut_ptr(), 2, 7)
+        .build()
+        .user_data(0x6a)
+        .into();
+
+    drop(dst);
+    drop(pool);
+    write.write_all(b"yo")?;
+
+    unsafe {
+        ring.submission().push(&read_e).expect("queue is full");
+    }
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.result(), -EFAULT);
+
+    ring.submitter().unregister_buffers()?;
+
+    Ok(())
+}
+
 /// Create a pipe and return both ends as RAII `File` handles
+pub fn test_registered_buffers_owned<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require!(
+        test;
+        test.probe.is_supported(ReadFixed::CODE);
+        test.probe.is_supported(WriteFixed::CODE);
+        ring.params().is_feature_resource_tagging();
+    );
+
+    println!("test registered_buffers_owned");
+
+    let pool = RegisteredBuffers::builder()
+        .buf_cnt(2)
+        .buf_len(64)
+        .build(&ring.submitter())?;
+
+    let file = tempfile::tempfile()?;
+    let fd = Fd(file.as_raw_fd());
+
+    let text = b"The quick brown fox jumps over the lazy dog.";
+
+    let mut src = pool.checkout().unwrap();
+    src[..text.len()].copy_from_slice(text);
+    let write = BufOp::write(fd, src.slice(..text.len()), 0);
+    assert_eq!(write.entry().get_opcode(), WriteFixed::CODE as u32);
+    let (res, _src) = utils::complete_owned(ring, write)?;
+    assert_eq!(res?, text.len());
+
+    let dst = pool.checkout().unwrap();
+    let read = BufOp::read(fd, dst.slice(10..), 4);
+    assert_eq!(read.entry().get_opcode(), ReadFixed::CODE as u32);
+    let (res, dst) = utils::complete_owned(ring, read)?;
+    assert_eq!(res?, text.len() - 4);
+    assert_eq!(&dst.get_ref()[10..10 + text.len() - 4], &text[4..]);
+
+    Ok(())
+}
+
 fn create_pipe() -> io::Result<(File, File)> {
     let mut fds = [-1, -1];
 
@@ -2,6 +2,7 @@ use std::io;
 use std::os::fd::AsRawFd;
 use std::os::fd::FromRawFd;
 use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
 
 use io_uring::cqueue;
 use io_uring::opcode;
@@ -221,6 +222,102 @@ pub fn test_register_sync_cancel_unsubmitted<S: squeue::EntryMarker, C: cqueue::
     Ok(())
 }
 
+pub fn test_register_sync_cancel_fd_opcode<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> io::Result<()> {
+    require!(
+        test; // We need at least 6.6 to match on the opcode. opcode::FutexWait is a proxy for that requirement.
+        test.probe.is_supported(opcode::Recv::CODE);
+        test.probe.is_supported(opcode::PollAdd::CODE);
+        test.probe.is_supported(opcode::FutexWait::CODE);
+    );
+
+    let (sock_1, sock_2) = UnixStream::pair()?;
+    let fd_1 = types::Fd(sock_1.as_raw_fd());
+    let fd_2 = types::Fd(sock_2.as_raw_fd());
+
+    // 2 receives on sock_1, canceled by fd and opcode.
+    const USER_DATA_0: u64 = 48u64;
+    // A poll on sock_1, which has to survive the above.
+    const USER_DATA_1: u64 = 49u64;
+    // 2 receives on sock_2, of which one is canceled by opcode and user_data.
+    const USER_DATA_2: u64 = 50u64;
+    const USER_DATA_3: u64 = 51u64;
+
+    let mut buf = [0u8; 32];
+    let entries = vec![
+        opcode::Recv::new(fd_1, buf.as_mut_ptr(), 32)
+            .build()
+            .user_data(USER_DATA_0),
+        opcode::Recv::new(fd_1, buf.as_mut_ptr(), 32)
+            .build()
+            .user_data(USER_DATA_0),
+        opcode::PollAdd::new(fd_1, libc::POLLIN as _)
+            .build()
+            .user_data(USER_DATA_1),
+        opcode::Recv::new(fd_2, buf.as_mut_ptr(), 32)
+            .build()
+            .user_data(USER_DATA_2),
+        opcode::Recv::new(fd_2, buf.as_mut_ptr(), 32)
+            .build()
+            .user_data(USER_DATA_3),
+    ];
+    for entry in entries {
+        unsafe { ring.submission().push(&entry.into()).unwrap() };
+    }
+    assert_eq!(5, ring.submit()?);
+
+    // Cancel the receives on sock_1, but not the poll.
+    ring.submitter().register_sync_cancel(
+        None,
+        CancelBuilder::fd(fd_1)
+            .with_opcode(opcode::Recv::CODE)
+            .all(),
+    )?;
+    let completions = wait_get_completions(ring, 2)?;
+    assert_eq!(completions.len(), 2);
+    for completion in completions {
+        assert_eq!(completion.user_data(), USER_DATA_0);
+        assert_eq!(completion.result(), -libc::ECANCELED);
+    }
+
+    // Cancel a single receive on sock_2 by opcode and user_data.
+    ring.submitter().register_sync_cancel(
+        None,
+        CancelBuilder::opcode(opcode::Recv::CODE).with_user_data(USER_DATA_3),
+    )?;
+    let completions = wait_get_completions(ring, 1)?;
+    assert_eq!(completions.len(), 1);
+    assert_eq!(completions[0].user_data(), USER_DATA_3);
+    assert_eq!(completions[0].result(), -libc::ECANCELED);
+
+    // Nothing else matches these.
+    let result = ring.submitter().register_sync_cancel(
+        None,
+        CancelBuilder::opcode(opcode::Recv::CODE)
+            .with_fd(fd_1)
+            .with_user_data(USER_DATA_2),
+    );
+    assert!(matches!(
+        result.err().unwrap().kind(),
+        io::ErrorKind::NotFound
+    ));
+
+    // Cancel the remaining poll and receive.
+    ring.submitter()
+        .register_sync_cancel(None, CancelBuilder::any().all())?;
+    let completions = wait_get_completions(ring, 2)?;
+    let mut user_data_entries = completions
+        .iter()
+        .map(|c| c.user_data())
+        .collect::<Vec<u64>>();
+    user_data_entries.sort();
+    assert_eq!(user_data_entries, vec![USER_DATA_1, USER_DATA_2]);
+
+    Ok(())
+}
+
 /// Blocks for a short amount of time, waiting for completions to arrive.
 ///
 /// Returns all completions that have arrived.
@@ -434,6 +434,136 @@ pub fn test_timeout_submit_args_min_wait<S: squeue::EntryMarker, C: cqueue::Entr
     Ok(())
 }
 
+pub fn test_timeout_submit_registered_args<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    _ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require! {
+        test;
+    };
+
+    println!("test timeout_submit_registered_args");
+
+    let mut ring = IoUring::<S, C>::builder().setup_r_disabled().build(8)?;
+
+    let mut region = match ring.submitter().register_mem_region(4) {
+        Ok(region) => region,
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+            println!("IORING_REGISTER_MEM_REGION is not supported by the kernel, skip");
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    ring.submitter().register_enable_rings()?;
+
+    assert_eq!(region.len(), 4);
+    region.slots_mut()[2].set_timespec(Some(types::Timespec::new().sec(1)));
+
+    // timeout
+
+    let start = Instant::now();
+    match ring.submitter().submit_with_registered_args(1, 2) {
+        Ok(_) => panic!(),
+        Err(ref err) if err.raw_os_error() == Some(libc::ETIME) => (),
+        Err(err) => return Err(err.into()),
+    }
+    assert_eq!(start.elapsed().as_secs(), 1);
+
+    assert!(ring.completion().next().is_none());
+
+    // no timeout
+
+    let nop_e = opcode::Nop::new();
+
+    unsafe {
+        ring.submission()
+            .push(&nop_e.build().user_data(0x1e).into())
+            .expect("queue is full");
+    }
+
+    let start = Instant::now();
+    ring.submitter().submit_with_registered_args(1, 2)?;
+    assert_eq!(start.elapsed().as_secs(), 0);
+
+    let cqes: Vec<cqueue::Entry> = ring.completion().map(Into::into).collect();
+
+    assert_eq!(cqes.len(), 1);
+    assert_eq!(cqes[0].user_data(), 0x1e);
+    assert_eq!(cqes[0].result(), 0);
+
+    // out of bounds slot
+
+    match ring.submitter().submit_with_registered_args(1, 4096) {
+        Ok(_) => panic!(),
+        Err(ref err) if err.raw_os_error() == Some(libc::EFAULT) => (),
+        Err(err) => return Err(err.into()),
+    }
+
+    Ok(())
+}
+
+pub fn test_timeout_submit_args_abs<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    _ring: &mut IoUring<S, C>,
+    test: &Test,
+) -> anyhow::Result<()> {
+    require! {
+        test;
+    };
+
+    println!("test timeout_submit_args_abs");
+
+    let mut ring = IoUring::<S, C>::builder().build(8)?;
+
+    match ring.submitter().register_clock(types::Clock::Boottime) {
+        Ok(()) => (),
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+            println!("IORING_REGISTER_CLOCK is not supported by the kernel, skip");
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    // Taken before the deadline is computed, so that at least a second has passed when it expires.
+    let start = Instant::now();
+
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    assert_eq!(
+        unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut now) },
+        0
+    );
+
+    let ts = types::Timespec::new()
+        .sec(now.tv_sec as u64 + 1)
+        .nsec(now.tv_nsec as u32);
+    let args = types::SubmitArgs::new().timespec(&ts).abs_timer();
+
+    // deadline
+
+    match ring.submitter().submit_with_args(1, &args) {
+        Ok(_) => panic!(),
+        Err(ref err) if err.raw_os_error() == Some(libc::ETIME) => (),
+        Err(err) => return Err(err.into()),
+    }
+    assert_eq!(start.elapsed().as_secs(), 1);
+
+    // deadline in the past
+
+    let start = Instant::now();
+    match ring.submitter().submit_with_args(1, &args) {
+        Ok(_) => panic!(),
+        Err(ref err) if err.raw_os_error() == Some(libc::ETIME) => (),
+        Err(err) => return Err(err.into()),
+    }
+    assert_eq!(start.elapsed().as_secs(), 0);
+
+    assert!(ring.completion().next().is_none());
+
+    Ok(())
+}
+
 pub fn test_timeout_multishot<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     test: &Test,
@@ -1,3 +1,4 @@
+use io_uring::buf::OwnedOp;
 use io_uring::{cqueue, opcode, squeue, types, IoUring};
 use std::io::{IoSlice, IoSliceMut};
 
@@ -53,6 +54,27 @@ pub fn type_name_of<T>(_f: T) -> &'static str {
     std::any::type_name::<T>()
 }
 
+/// Submit `op` on its own and complete it.
+pub fn complete_owned<S: squeue::EntryMarker, C: cqueue::EntryMarker, T: OwnedOp>(
+    ring: &mut IoUring<S, C>,
+    op: T,
+) -> anyhow::Result<T::Output> {
+    let entry = op.entry().clone().user_data(0x42);
+
+    unsafe {
+        ring.submission()
+            .push(&entry.into())
+            .expect("queue is full");
+    }
+
+    ring.submit_and_wait(1)?;
+
+    let cqe: cqueue::Entry = ring.completion().next().expect("cqueue is empty").into();
+    assert_eq!(cqe.user_data(), 0x42);
+
+    Ok(unsafe { op.complete(&cqe) })
+}
+
 pub fn write_read<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
     ring: &mut IoUring<S, C>,
     fd_in: types::Fd,
@@ -0,0 +1,484 @@
+//! Owned buffers.
+//!
+//! The requests in [`opcode`] take raw pointers, so whoever submits them must keep the memory
+//! alive and in place until they complete. [`IoBuf`] and [`IoBufMut`] describe buffers whose
+//! memory stays put when they are moved, so that a request can take ownership of its buffer:
+//! a [`BufOp`] holds a request along with its buffer, and hands the buffer back with the result
+//! once the request has completed. [`SelectOp`] does the same for requests picking their buffer
+//! from a [`BufRing`].
+//!
+//! Requests owning their memory implement [`OwnedOp`], which lets them be kept in an
+//! `OpRegistry`, available with the `registry` feature, until they complete.
+
+use std::ops::{Bound, RangeBounds};
+use std::{cmp, io, ptr};
+
+use crate::buf_ring::{self, BufRing};
+use crate::registered_buffers::FixedBuf;
+use crate::types::sealed;
+use crate::{cqueue, opcode, squeue};
+
+/// A buffer that requests can read from.
+///
+/// # Safety
+///
+/// The memory of the buffer must stay valid and must not move as long as the buffer is alive,
+/// even if the buffer itself is moved. The first [`bytes_init`](Self::bytes_init) bytes from
+/// [`stable_ptr`](Self::stable_ptr) must be initialized, and all
+/// [`bytes_total`](Self::bytes_total) bytes valid to write for [`IoBufMut`] buffers.
+pub unsafe trait IoBuf: 'static {
+    /// A pointer to the start of the buffer.
+    fn stable_ptr(&self) -> *const u8;
+
+    /// The number of initialized bytes, which is what a write sends.
+    fn bytes_init(&self) -> usize;
+
+    /// The size of the buffer, which is what a read may fill.
+    fn bytes_total(&self) -> usize;
+
+    /// The index of the buffer if it is registered with the kernel, in which case reads and
+    /// writes use [`ReadFixed`](opcode::ReadFixed) and [`WriteFixed`](opcode::WriteFixed).
+    fn buf_index(&self) -> Option<u16> {
+        None
+    }
+
+    /// Restrict the buffer to `range`, relative to its start.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds of [`bytes_total`](Self::bytes_total).
+    fn slice(self, range: impl RangeBounds<usize>) -> Slice<Self>
+    where
+        Self: Sized,
+    {
+        let begin = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.checked_add(1).expect("out of range"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.checked_add(1).expect("out of range"),
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.bytes_total(),
+        };
+        assert!(
+            begin <= end && end <= self.bytes_total(),
+            "range out of bounds"
+        );
+
+        Slice {
+            buf: self,
+            begin,
+            end,
+        }
+    }
+}
+
+/// A buffer that requests can read into.
+///
+/// # Safety
+///
+/// See [`IoBuf`].
+pub unsafe trait IoBufMut: IoBuf {
+    /// A mutable pointer to the start of the buffer.
+    fn stable_mut_ptr(&mut self) -> *mut u8;
+
+    /// Mark the first `pos` bytes as initialized, if they were not already.
+    ///
+    /// # Safety
+    ///
+    /// The first `pos` bytes must have been initialized.
+    unsafe fn set_init(&mut self, pos: usize);
+}
+
+/// A sub-range of a buffer, created by [`IoBuf::slice`].
+///
+/// Reads into a slice only mark its bytes as initialized in the whole buffer if the slice starts
+/// within the initialized part of the buffer, as the bytes in between would not be.
+#[derive(Debug)]
+pub struct Slice<T> {
+    buf: T,
+    begin: usize,
+    end: usize,
+}
+
+impl<T> Slice<T> {
+    /// The offset of the slice in the buffer.
+    pub fn begin(&self) -> usize {
+        self.begin
+    }
+
+    /// The end of the slice in the buffer.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// The whole buffer.
+    pub fn get_ref(&self) -> &T {
+        &self.buf
+    }
+
+    /// The whole buffer.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.buf
+    }
+
+    /// Take back the whole buffer.
+    pub fn into_inner(self) -> T {
+        self.buf
+    }
+}
+
+unsafe impl<T: IoBuf> IoBuf for Slice<T> {
+    fn stable_ptr(&self) -> *const u8 {
+        unsafe { self.buf.stable_ptr().add(self.begin) }
+    }
+
+    fn bytes_init(&self) -> usize {
+        cmp::min(self.buf.bytes_init(), self.end).saturating_sub(self.begin)
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.end - self.begin
+    }
+
+    fn buf_index(&self) -> Option<u16> {
+        self.buf.buf_index()
+    }
+}
+
+unsafe impl<T: IoBufMut> IoBufMut for Slice<T> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        unsafe { self.buf.stable_mut_ptr().add(self.begin) }
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        if self.begin <= self.buf.bytes_init() {
+            self.buf.set_init(self.begin + pos);
+        }
+    }
+}
+
+/// The length of a `Vec<u8>` is the number of initialized bytes, and reads fill its capacity.
+unsafe impl IoBuf for Vec<u8> {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.capacity()
+    }
+}
+
+unsafe impl IoBufMut for Vec<u8> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        if self.len() < pos {
+            self.set_len(pos);
+        }
+    }
+}
+
+unsafe impl IoBuf for Box<[u8]> {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBufMut for Box<[u8]> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+
+    unsafe fn set_init(&mut self, _pos: usize) {}
+}
+
+unsafe impl IoBuf for &'static [u8] {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for &'static str {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+/// The memory of a registered buffer is zeroed when the pool is created, so all of it is
+/// initialized.
+unsafe impl IoBuf for FixedBuf {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_mut_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+
+    fn buf_index(&self) -> Option<u16> {
+        Some(FixedBuf::buf_index(self))
+    }
+}
+
+unsafe impl IoBufMut for FixedBuf {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+
+    unsafe fn set_init(&mut self, _pos: usize) {}
+}
+
+/// A buffer taken from a [`BufRing`] holds the data of the completion it was taken for, which
+/// can be written out without copying it.
+unsafe impl IoBuf for buf_ring::Buffer {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+/// A request that owns the memory it refers to.
+///
+/// # Safety
+///
+/// The memory that [`entry`](Self::entry) refers to must stay valid and must not move as long as
+/// the request is alive, even if the request itself is moved.
+pub unsafe trait OwnedOp {
+    /// What the request completes with.
+    type Output;
+
+    /// The submission queue entry of the request.
+    fn entry(&self) -> &squeue::Entry;
+
+    /// Complete the request with `cqe`, handing back what it owns.
+    ///
+    /// # Safety
+    ///
+    /// `cqe` must be the last completion of the request, submitted with [`entry`](Self::entry),
+    /// as what it reports is trusted, such as the number of bytes read into the buffer.
+    unsafe fn complete(self, cqe: &cqueue::Entry) -> Self::Output;
+}
+
+/// A read or write request owning its buffer.
+///
+/// The buffer is handed back by [`complete`](OwnedOp::complete) along with the number of bytes
+/// transferred. For reads, that many bytes from the start of the buffer are marked as
+/// initialized, which for a `Vec<u8>` grows its length to at least the number of bytes read.
+pub struct BufOp<B> {
+    entry: squeue::Entry,
+    buf: B,
+    set_init: Option<fn(&mut B, usize)>,
+}
+
+impl<B: IoBufMut> BufOp<B> {
+    /// Read from `fd` at `offset` into the buffer, with a [`Read`](opcode::Read), or a
+    /// [`ReadFixed`](opcode::ReadFixed) for registered buffers.
+    pub fn read(fd: impl sealed::UseFixed, mut buf: B, offset: u64) -> BufOp<B> {
+        let ptr = buf.stable_mut_ptr();
+        let len = buf.bytes_total() as u32;
+        let entry = match buf.buf_index() {
+            Some(buf_index) => opcode::ReadFixed::new(fd, ptr, len, buf_index)
+                .offset(offset)
+                .build(),
+            None => opcode::Read::new(fd, ptr, len).offset(offset).build(),
+        };
+        BufOp::reading(entry, buf)
+    }
+
+    /// Receive from `fd` into the buffer, with a [`Recv`](opcode::Recv).
+    pub fn recv(fd: impl sealed::UseFixed, mut buf: B) -> BufOp<B> {
+        let entry = opcode::Recv::new(fd, buf.stable_mut_ptr(), buf.bytes_total() as u32).build();
+        BufOp::reading(entry, buf)
+    }
+
+    fn reading(entry: squeue::Entry, buf: B) -> BufOp<B> {
+        BufOp {
+            entry,
+            buf,
+            // Safety: the kernel has initialized the bytes it read.
+            set_init: Some(|buf, n| unsafe { buf.set_init(n) }),
+        }
+    }
+}
+
+impl<B: IoBuf> BufOp<B> {
+    /// Write the initialized bytes of the buffer to `fd` at `offset`, with a
+    /// [`Write`](opcode::Write), or a [`WriteFixed`](opcode::WriteFixed) for registered buffers.
+    pub fn write(fd: impl sealed::UseFixed, buf: B, offset: u64) -> BufOp<B> {
+        let ptr = buf.stable_ptr();
+        let len = buf.bytes_init() as u32;
+        let entry = match buf.buf_index() {
+            Some(buf_index) => opcode::WriteFixed::new(fd, ptr, len, buf_index)
+                .offset(offset)
+                .build(),
+            None => opcode::Write::new(fd, ptr, len).offset(offset).build(),
+        };
+        BufOp::writing(entry, buf)
+    }
+
+    /// Send the initialized bytes of the buffer to `fd`, with a [`Send`](opcode::Send).
+    pub fn send(fd: impl sealed::UseFixed, buf: B) -> BufOp<B> {
+        let entry = opcode::Send::new(fd, buf.stable_ptr(), buf.bytes_init() as u32).build();
+        BufOp::writing(entry, buf)
+    }
+
+    fn writing(entry: squeue::Entry, buf: B) -> BufOp<B> {
+        BufOp {
+            entry,
+            buf,
+            set_init: None,
+        }
+    }
+
+    /// Set the [flags](squeue::Flags) of the request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `flags` has any flag other than [`IO_LINK`](squeue::Flags::IO_LINK),
+    /// [`IO_HARDLINK`](squeue::Flags::IO_HARDLINK), [`IO_DRAIN`](squeue::Flags::IO_DRAIN) and
+    /// [`ASYNC`](squeue::Flags::ASYNC), as the others change what the request uses, such as
+    /// [`BUFFER_SELECT`](squeue::Flags::BUFFER_SELECT) reading into a buffer it does not own.
+    pub fn flags(mut self, flags: squeue::Flags) -> BufOp<B> {
+        self.entry = with_flags(self.entry, flags);
+        self
+    }
+
+    /// The buffer of the request.
+    pub fn buf(&self) -> &B {
+        &self.buf
+    }
+}
+
+unsafe impl<B: IoBuf> OwnedOp for BufOp<B> {
+    type Output = (io::Result<usize>, B);
+
+    fn entry(&self) -> &squeue::Entry {
+        &self.entry
+    }
+
+    unsafe fn complete(mut self, cqe: &cqueue::Entry) -> Self::Output {
+        let res = cqueue::decode_len(cqe);
+        if let (Ok(n), Some(set_init)) = (&res, self.set_init) {
+            set_init(&mut self.buf, *n);
+        }
+        (res, self.buf)
+    }
+}
+
+/// A read or receive request picking its buffer from a [`BufRing`].
+///
+/// [`complete`](OwnedOp::complete) hands over the [`Buffer`](buf_ring::Buffer) the kernel picked,
+/// or `None` if it picked none, which it may do when the request reads nothing, such as at the
+/// end of a file or stream.
+pub struct SelectOp {
+    entry: squeue::Entry,
+    ring: BufRing,
+}
+
+impl SelectOp {
+    /// Read up to `len` bytes from `fd` at `offset` into a buffer of `ring`, with a
+    /// [`Read`](opcode::Read).
+    pub fn read(fd: impl sealed::UseFixed, ring: &BufRing, len: u32, offset: u64) -> SelectOp {
+        let entry = opcode::Read::new(fd, ptr::null_mut(), len)
+            .offset(offset)
+            .buf_group(ring.bgid())
+            .build();
+        SelectOp::new(entry, ring)
+    }
+
+    /// Receive from `fd` into a buffer of `ring`, with a [`Recv`](opcode::Recv).
+    pub fn recv(fd: impl sealed::UseFixed, ring: &BufRing) -> SelectOp {
+        let entry = opcode::Recv::new(fd, ptr::null_mut(), ring.buf_len() as u32)
+            .buf_group(ring.bgid())
+            .build();
+        SelectOp::new(entry, ring)
+    }
+
+    fn new(entry: squeue::Entry, ring: &BufRing) -> SelectOp {
+        SelectOp {
+            entry: entry.flags(squeue::Flags::BUFFER_SELECT),
+            ring: ring.clone(),
+        }
+    }
+
+    /// Set the [flags](squeue::Flags) of the request.
+    ///
+    /// # Panics
+    ///
+    /// See [`BufOp::flags`].
+    pub fn flags(mut self, flags: squeue::Flags) -> SelectOp {
+        self.entry = with_flags(self.entry, flags);
+        self
+    }
+}
+
+// Safety: the buffers of the ring outlive the request, as it keeps the ring alive.
+unsafe impl OwnedOp for SelectOp {
+    type Output = io::Result<Option<buf_ring::Buffer>>;
+
+    fn entry(&self) -> &squeue::Entry {
+        &self.entry
+    }
+
+    unsafe fn complete(self, cqe: &cqueue::Entry) -> Self::Output {
+        let n = cqueue::decode_len(cqe)?;
+        // Safety: `cqe` is the last completion of the request, which selected from the ring.
+        Ok(self.ring.get(n as u32, cqe.flags()))
+    }
+}
+
+/// Add `flags` to `entry`, as long as they only link, drain or offload the request.
+fn with_flags(entry: squeue::Entry, flags: squeue::Flags) -> squeue::Entry {
+    let allowed = squeue::Flags::IO_LINK
+        | squeue::Flags::IO_HARDLINK
+        | squeue::Flags::IO_DRAIN
+        | squeue::Flags::ASYNC;
+    assert!(
+        allowed.contains(flags),
+        "flags not allowed on owned requests"
+    );
+    entry.flags(flags)
+}
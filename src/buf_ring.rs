@@ -0,0 +1,414 @@
+//! Provided buffer rings.
+//!
+//! A [`BufRing`] owns a ring of buffer descriptors together with the buffers themselves, and
+//! registers them with the kernel as a buffer group. Operations that set
+//! [`BUFFER_SELECT`](crate::squeue::Flags::BUFFER_SELECT) and the ring's
+//! [`bgid`](BufRing::bgid) let the kernel pick a buffer when data is ready, and the completion
+//! tells which one was picked. [`BufRing::get`] turns that completion into a [`Buffer`] which
+//! hands the buffer back to the kernel when dropped.
+//!
+//! [`SelectOp`](crate::buf::SelectOp) does this safely, as it keeps the ring alive until its
+//! request has completed, and takes the buffer from its own completion.
+
+use std::cell::Cell;
+use std::os::unix::io::AsRawFd;
+use std::rc::Rc;
+use std::sync::atomic::{self, AtomicU16};
+use std::{fmt, io, mem, ops, slice};
+
+use crate::register::{execute, RegisterRing};
+use crate::types::BufRingEntry;
+use crate::util::{Mmap, OwnedFd};
+use crate::{cqueue, sys, Submitter};
+
+/// A ring of provided buffers registered with the kernel.
+///
+/// The ring is unregistered and its memory released once the `BufRing` and all the [`Buffer`]s
+/// taken from it have been dropped, see [`Builder::build`] for what that requires of the
+/// operations selecting from it.
+///
+/// Cloning a `BufRing` is cheap and refers to the same ring.
+#[derive(Clone)]
+pub struct BufRing {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    bgid: u16,
+    mask: u16,
+    buf_cnt: u16,
+    buf_len: usize,
+
+    // The descriptors shared with the kernel, followed by the buffers they point into.
+    ring: Mmap,
+    bufs: Mmap,
+
+    // The tail that has been written locally but not yet made visible to the kernel.
+    local_tail: Cell<u16>,
+    tail: *const AtomicU16,
+
+    slots: Box<[Slot]>,
+
+    // A duplicate of the ring file descriptor, so that the buffer group can be unregistered
+    // before its memory is released even if the `IoUring` has already been dropped.
+    fd: OwnedFd,
+}
+
+// The state of a buffer that the kernel has picked for one or more completions.
+#[derive(Default)]
+struct Slot {
+    // How much of the buffer has been handed out, which is more than one completion's worth when
+    // consuming incrementally.
+    consumed: Cell<usize>,
+    // The number of live `Buffer`s referring to the buffer.
+    outstanding: Cell<u32>,
+    // Whether the kernel is done with the buffer, so that it can be recycled once the last
+    // `Buffer` is dropped.
+    finished: Cell<bool>,
+    // Whether the buffer has been kept out of the ring with `Buffer::forget`.
+    forgotten: Cell<bool>,
+}
+
+/// Builder for a [`BufRing`].
+#[derive(Debug, Clone, Copy)]
+pub struct Builder {
+    bgid: u16,
+    ring_entries: u16,
+    buf_cnt: u16,
+    buf_len: usize,
+    incremental: bool,
+}
+
+impl Builder {
+    /// Set the number of entries in the ring, which must be a power of two no larger than 32768.
+    /// Defaults to 128.
+    pub fn ring_entries(&mut self, ring_entries: u16) -> &mut Self {
+        self.ring_entries = ring_entries;
+        self
+    }
+
+    /// Set the number of buffers, which must not be larger than the number of entries. Defaults
+    /// to the number of entries.
+    pub fn buf_cnt(&mut self, buf_cnt: u16) -> &mut Self {
+        self.buf_cnt = buf_cnt;
+        self
+    }
+
+    /// Set the length of each buffer. Defaults to 4096.
+    pub fn buf_len(&mut self, buf_len: usize) -> &mut Self {
+        self.buf_len = buf_len;
+        self
+    }
+
+    /// Let the kernel consume buffers incrementally, so that a completion may only use part of a
+    /// buffer and leave the rest for later completions. Such completions are flagged with
+    /// [`buffer_more`](cqueue::buffer_more). Available since 6.12.
+    pub fn incremental(&mut self, incremental: bool) -> &mut Self {
+        self.incremental = incremental;
+        self
+    }
+
+    /// Allocate the ring and its buffers, register them with the ring of `submitter` and hand
+    /// all the buffers to the kernel.
+    ///
+    /// Fails with `InvalidInput` if the sizes are invalid.
+    ///
+    /// # Safety
+    ///
+    /// Operations selecting from the ring must have completed by the time the returned
+    /// [`BufRing`], its clones and all the [`Buffer`]s taken from it have been dropped, as the
+    /// kernel may otherwise write into released memory. A [`SelectOp`](crate::buf::SelectOp)
+    /// holds a clone of the ring until it is completed, so this is only a concern for
+    /// operations submitted otherwise.
+    pub unsafe fn build(&self, submitter: &Submitter<'_>) -> io::Result<BufRing> {
+        let ring_entries = self.ring_entries;
+        let buf_cnt = if self.buf_cnt == 0 {
+            ring_entries
+        } else {
+            self.buf_cnt
+        };
+
+        if !ring_entries.is_power_of_two()
+            || ring_entries > 1 << 15
+            || buf_cnt > ring_entries
+            || self.buf_len == 0
+            || self.buf_len > u32::MAX as usize
+        {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+
+        // Both need to be page aligned and zeroed, which anonymous mappings are.
+        let ring = Mmap::new_anon(ring_entries as usize * mem::size_of::<BufRingEntry>())?;
+        let bufs = Mmap::new_anon(buf_cnt as usize * self.buf_len)?;
+
+        let fd = submitter.dup_fd()?;
+
+        let flags = if self.incremental {
+            sys::IOU_PBUF_RING_INC as u16
+        } else {
+            0
+        };
+
+        // Safety: the memory stays valid until the group is unregistered when `Inner` is dropped.
+        unsafe {
+            submitter.register_buf_ring_with_flags(
+                ring.as_mut_ptr() as _,
+                ring_entries,
+                self.bgid,
+                flags,
+            )?;
+        }
+
+        let tail = unsafe { BufRingEntry::tail(ring.as_mut_ptr().cast()) }.cast::<AtomicU16>();
+        let inner = Inner {
+            bgid: self.bgid,
+            mask: ring_entries - 1,
+            buf_cnt,
+            buf_len: self.buf_len,
+            ring,
+            bufs,
+            local_tail: Cell::new(0),
+            tail,
+            slots: (0..buf_cnt).map(|_| Slot::default()).collect(),
+            fd,
+        };
+
+        for bid in 0..buf_cnt {
+            unsafe { inner.add(bid) };
+        }
+        inner.advance();
+
+        Ok(BufRing {
+            inner: Rc::new(inner),
+        })
+    }
+}
+
+impl BufRing {
+    /// Create a [`Builder`] for a ring with buffer group id `bgid`, which must not be in use by
+    /// another buffer group of the same io_uring instance.
+    pub fn builder(bgid: u16) -> Builder {
+        Builder {
+            bgid,
+            ring_entries: 128,
+            buf_cnt: 0,
+            buf_len: 4096,
+            incremental: false,
+        }
+    }
+
+    /// The buffer group id to select buffers from this ring with.
+    pub fn bgid(&self) -> u16 {
+        self.inner.bgid
+    }
+
+    /// The length of each buffer.
+    pub fn buf_len(&self) -> usize {
+        self.inner.buf_len
+    }
+
+    /// The number of buffers.
+    pub fn buf_cnt(&self) -> u16 {
+        self.inner.buf_cnt
+    }
+
+    /// Take the buffer that the kernel selected for a completion with result `len` and `flags`.
+    ///
+    /// Returns `None` if the completion has no buffer. The buffer is handed back to the kernel
+    /// when the returned [`Buffer`] is dropped. If the completion only consumed part of it, see
+    /// [`Builder::incremental`], that happens once the kernel is done with the buffer and all
+    /// [`Buffer`]s referring to it have been dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer id or `len` is out of bounds for this ring, which happens if the
+    /// completion selected from another buffer group.
+    ///
+    /// # Safety
+    ///
+    /// `len` and `flags` must be the result and flags of a completion of an operation that
+    /// selected from this ring, and each completion must only be taken once. Otherwise the
+    /// returned [`Buffer`] may refer to a buffer that the kernel is still writing into, or that
+    /// another [`Buffer`] refers to as well.
+    pub unsafe fn get(&self, len: u32, flags: u32) -> Option<Buffer> {
+        let bid = cqueue::buffer_select(flags)?;
+        assert!(bid < self.inner.buf_cnt, "buffer id out of bounds");
+
+        let slot = &self.inner.slots[bid as usize];
+        let offset = slot.consumed.get();
+        let len = len as usize;
+        assert!(offset + len <= self.inner.buf_len, "length out of bounds");
+
+        let partial = cqueue::buffer_more(flags);
+        slot.consumed.set(offset + len);
+        slot.outstanding.set(slot.outstanding.get() + 1);
+        slot.finished.set(!partial);
+
+        Some(Buffer {
+            ring: self.inner.clone(),
+            bid,
+            offset,
+            len,
+            partial,
+        })
+    }
+
+    /// Write buffer `bid` to the ring without making it visible to the kernel, see
+    /// [`advance`](Self::advance).
+    ///
+    /// # Safety
+    ///
+    /// The buffer must not be in the ring already, and must not be in use by the kernel or
+    /// through a [`Buffer`]: only use this for buffers kept out of the ring with
+    /// [`Buffer::forget`], once all other [`Buffer`]s referring to them have been dropped.
+    pub unsafe fn add(&self, bid: u16) {
+        assert!(bid < self.inner.buf_cnt, "buffer id out of bounds");
+        self.inner.slots[bid as usize].reset();
+        self.inner.add(bid);
+    }
+
+    /// Make the buffers written with [`add`](Self::add) visible to the kernel.
+    pub fn advance(&self) {
+        self.inner.advance();
+    }
+}
+
+impl fmt::Debug for BufRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufRing")
+            .field("bgid", &self.inner.bgid)
+            .field("ring_entries", &(self.inner.mask as u32 + 1))
+            .field("buf_cnt", &self.inner.buf_cnt)
+            .field("buf_len", &self.inner.buf_len)
+            .finish()
+    }
+}
+
+impl Inner {
+    fn buf_ptr(&self, bid: u16) -> *mut u8 {
+        unsafe {
+            self.bufs
+                .as_mut_ptr()
+                .cast::<u8>()
+                .add(bid as usize * self.buf_len)
+        }
+    }
+
+    // The tail may exceed the number of entries and wrap around, which is how the kernel tells
+    // a full ring from an empty one; only the index of the entry is masked.
+    unsafe fn add(&self, bid: u16) {
+        let tail = self.local_tail.get();
+        self.local_tail.set(tail.wrapping_add(1));
+
+        let entry = &mut *self
+            .ring
+            .as_mut_ptr()
+            .cast::<BufRingEntry>()
+            .add((tail & self.mask) as usize);
+        entry.set_addr(self.buf_ptr(bid) as _);
+        entry.set_len(self.buf_len as _);
+        entry.set_bid(bid);
+    }
+
+    fn advance(&self) {
+        // Release, so that the kernel sees the entries written before the tail.
+        unsafe { (*self.tail).store(self.local_tail.get(), atomic::Ordering::Release) };
+    }
+
+    // Drop a reference to buffer `bid`, and hand it back to the kernel if it was the last one.
+    fn release(&self, bid: u16) {
+        let slot = &self.slots[bid as usize];
+        slot.outstanding.set(slot.outstanding.get() - 1);
+
+        if slot.outstanding.get() == 0 && slot.finished.get() && !slot.forgotten.get() {
+            slot.reset();
+            unsafe { self.add(bid) };
+            self.advance();
+        }
+    }
+}
+
+impl Slot {
+    fn reset(&self) {
+        self.consumed.set(0);
+        self.finished.set(false);
+        self.forgotten.set(false);
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let arg = sys::io_uring_buf_reg {
+            bgid: self.bgid,
+            ..Default::default()
+        };
+
+        // Nothing can be done about a failure here, the group is gone along with the ring anyway.
+        let _ = execute(
+            RegisterRing::RawFd(self.fd.as_raw_fd()),
+            sys::IORING_UNREGISTER_PBUF_RING,
+            (&arg as *const sys::io_uring_buf_reg).cast(),
+            1,
+        );
+    }
+}
+
+/// A buffer taken from a [`BufRing`], which is handed back to the kernel when dropped.
+pub struct Buffer {
+    ring: Rc<Inner>,
+    bid: u16,
+    offset: usize,
+    len: usize,
+    partial: bool,
+}
+
+impl Buffer {
+    /// The buffer id.
+    pub fn bid(&self) -> u16 {
+        self.bid
+    }
+
+    /// Whether the kernel keeps using the rest of the buffer for later completions, in which case
+    /// it is not handed back on drop. See [`Builder::incremental`].
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+
+    /// Keep the buffer out of the ring instead of handing it back to the kernel, and return its
+    /// id. It can be handed back later with [`BufRing::add`].
+    pub fn forget(self) -> u16 {
+        self.ring.slots[self.bid as usize].forgotten.set(true);
+        self.bid
+    }
+}
+
+impl ops::Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ring.buf_ptr(self.bid).add(self.offset), self.len) }
+    }
+}
+
+impl ops::DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ring.buf_ptr(self.bid).add(self.offset), self.len) }
+    }
+}
+
+impl fmt::Debug for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Buffer")
+            .field("bgid", &self.ring.bgid)
+            .field("bid", &self.bid)
+            .field("len", &self.len)
+            .field("is_partial", &self.is_partial())
+            .finish()
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.ring.release(self.bid);
+    }
+}
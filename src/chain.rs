@@ -0,0 +1,289 @@
+//! Linked chains of requests.
+//!
+//! A [`Chain`] links a sequence of requests with [`IO_LINK`](Flags::IO_LINK) or
+//! [`IO_HARDLINK`](Flags::IO_HARDLINK), so that each request only starts once the previous one
+//! has completed, and pushes them to the submission queue all at once. The [`ChainCompletions`]
+//! returned by [`Chain::push`] tell which step of the chain each completion belongs to.
+//!
+//! A step can install a direct descriptor for the following steps to use, for example an
+//! [`OpenAt`](opcode::OpenAt) with a `file_index` followed by a [`Read`](opcode::Read) of the
+//! same [`Fixed`](crate::types::Fixed) file. This relies on the kernel looking up the file of a
+//! linked request only once it starts, see
+//! [`is_feature_linked_file`](crate::Parameters::is_feature_linked_file). A
+//! [`FixedFile`](crate::file_table::FixedFile) reserved up front gives the slot to install into,
+//! to use in the following steps, and to close at the end:
+//!
+//! ```no_run
+//! use io_uring::chain::Chain;
+//! use io_uring::file_table::FixedFileTable;
+//! use io_uring::{opcode, types, IoUring};
+//! use std::ffi::CString;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let mut ring = IoUring::new(8)?;
+//! let table = FixedFileTable::new(&ring.submitter(), 1)?;
+//!
+//! let file = table.reserve()?;
+//! let path = CString::new("README.md")?;
+//! let mut buf = vec![0; 1024];
+//!
+//! let open_e = opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), path.as_ptr())
+//!     .file_index(Some(file.dest()));
+//! let read_e = opcode::Read::new(file.fixed(), buf.as_mut_ptr(), buf.len() as _);
+//! let closing = file.close();
+//!
+//! // The file is closed even if reading it fails.
+//! let chain = Chain::new(open_e.build())
+//!     .then(read_e.build())
+//!     .then_hard(closing.entry());
+//!
+//! // Safety: the path and the buffer outlive the chain, which is waited for below.
+//! let mut completions =
+//!     unsafe { chain.push(&mut ring.submission(), 0x10) }.expect("queue is full");
+//! ring.submit_and_wait(chain.len())?;
+//!
+//! for cqe in ring.completion() {
+//!     completions.complete(&cqe);
+//! }
+//! closing.complete(completions.results()[2].unwrap());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use crate::squeue::{self, EntryMarker, Flags, PushError};
+use crate::{cqueue, opcode, SubmissionQueue};
+
+/// A sequence of requests to be linked together.
+///
+/// ```
+/// use io_uring::chain::Chain;
+/// use io_uring::{opcode, types};
+///
+/// let ts = types::Timespec::new().sec(1);
+/// let chain = Chain::new(opcode::Nop::new().build())
+///     .then(opcode::Nop::new().build())
+///     .link_timeout(opcode::LinkTimeout::new(&ts))
+///     .then_hard(opcode::Nop::new().build());
+/// assert_eq!(chain.len(), 4);
+/// ```
+#[derive(Clone)]
+pub struct Chain {
+    steps: Vec<Step>,
+}
+
+#[derive(Clone)]
+struct Step {
+    entry: squeue::Entry,
+    // The link to the next step, if any.
+    link: Flags,
+    link_timeout: bool,
+}
+
+/// Tracks the completions of a chain pushed with [`Chain::push`].
+#[derive(Clone)]
+pub struct ChainCompletions {
+    user_data: u64,
+    link_timeouts: Vec<bool>,
+    results: Vec<Option<i32>>,
+}
+
+/// The completion of a step of a chain, returned by [`ChainCompletions::complete`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainStep {
+    position: usize,
+    result: i32,
+    link_timeout: bool,
+}
+
+impl Chain {
+    /// Start a chain with `entry`.
+    ///
+    /// Any link flags already set on the entries of the chain are replaced by those of the chain,
+    /// other flags are kept.
+    pub fn new(entry: squeue::Entry) -> Chain {
+        Chain {
+            steps: vec![Step {
+                entry,
+                link: Flags::empty(),
+                link_timeout: false,
+            }],
+        }
+    }
+
+    /// Append `entry`, which starts once the previous step has completed successfully. If the
+    /// previous step fails, `entry` and all the steps after it complete with `-ECANCELED`.
+    pub fn then(self, entry: squeue::Entry) -> Chain {
+        self.append(entry, Flags::IO_LINK)
+    }
+
+    /// Append `entry`, which starts once the previous step has completed, whether it
+    /// succeeded or not.
+    pub fn then_hard(self, entry: squeue::Entry) -> Chain {
+        self.append(entry, Flags::IO_HARDLINK)
+    }
+
+    /// Bound the time the last step may take with `timeout`.
+    ///
+    /// If the timeout expires first, the last step is cancelled, which severs the link to the
+    /// steps after it unless they were appended with [`then_hard`](Self::then_hard).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the last step is a link timeout already.
+    pub fn link_timeout(mut self, timeout: opcode::LinkTimeout) -> Chain {
+        let last = self.steps.last_mut().unwrap();
+        assert!(!last.link_timeout, "a link timeout cannot follow another");
+        last.link = Flags::IO_LINK;
+
+        self.steps.push(Step {
+            entry: timeout.build(),
+            link: Flags::empty(),
+            link_timeout: true,
+        });
+        self
+    }
+
+    fn append(mut self, entry: squeue::Entry, link: Flags) -> Chain {
+        // The request guarded by a link timeout decides whether the link onward is severed,
+        // so it gets the same link as the timeout itself.
+        for step in self.steps.iter_mut().rev() {
+            step.link = link;
+            if !step.link_timeout {
+                break;
+            }
+        }
+
+        self.steps.push(Step {
+            entry,
+            link: Flags::empty(),
+            link_timeout: false,
+        });
+        self
+    }
+
+    /// The number of requests in the chain, including link timeouts.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Whether the chain is empty, which is never the case.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Push all the requests of the chain to `sq`, or none of them if there is not enough room.
+    ///
+    /// The requests are given consecutive user data starting from `user_data`, in the order of
+    /// the chain, which the returned [`ChainCompletions`] use to attribute completions to steps.
+    ///
+    /// # Safety
+    ///
+    /// Developers must ensure that parameters of all the entries (such as buffer) are valid and
+    /// will be valid for the entire duration of the chain, otherwise it may cause memory
+    /// problems.
+    ///
+    /// The user data already set on the entries is overwritten, so it cannot be relied upon to
+    /// find what a request refers to, such as memory to release on completion.
+    pub unsafe fn push<E: EntryMarker>(
+        &self,
+        sq: &mut SubmissionQueue<'_, E>,
+        user_data: u64,
+    ) -> Result<ChainCompletions, PushError> {
+        let link_flags = (Flags::IO_LINK | Flags::IO_HARDLINK).bits();
+        let entries: Vec<E> = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| {
+                let mut entry = step.entry.clone();
+                entry.0.flags = (entry.0.flags & !link_flags) | step.link.bits();
+                entry.0.user_data = user_data.wrapping_add(i as u64);
+                entry.into()
+            })
+            .collect();
+
+        sq.push_multiple(&entries)?;
+
+        Ok(ChainCompletions {
+            user_data,
+            link_timeouts: self.steps.iter().map(|step| step.link_timeout).collect(),
+            results: vec![None; self.steps.len()],
+        })
+    }
+}
+
+impl fmt::Debug for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.steps.iter().map(|step| &step.entry))
+            .finish()
+    }
+}
+
+impl ChainCompletions {
+    /// Account for a completion queue entry.
+    ///
+    /// Returns `None` if the entry does not belong to the chain, so that it can be handled
+    /// elsewhere.
+    pub fn complete(&mut self, cqe: &cqueue::Entry) -> Option<ChainStep> {
+        let position = cqe.user_data().wrapping_sub(self.user_data);
+        if position >= self.results.len() as u64 {
+            return None;
+        }
+
+        let position = position as usize;
+        self.results[position] = Some(cqe.result());
+
+        Some(ChainStep {
+            position,
+            result: cqe.result(),
+            link_timeout: self.link_timeouts[position],
+        })
+    }
+
+    /// Whether all the steps of the chain have completed.
+    pub fn is_done(&self) -> bool {
+        self.results.iter().all(Option::is_some)
+    }
+
+    /// The results of the steps of the chain, in order, or `None` for the steps that have not
+    /// completed yet.
+    pub fn results(&self) -> &[Option<i32>] {
+        &self.results
+    }
+}
+
+impl fmt::Debug for ChainCompletions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainCompletions")
+            .field("user_data", &self.user_data)
+            .field("results", &self.results)
+            .finish()
+    }
+}
+
+impl ChainStep {
+    /// The position of the step in the chain, counting link timeouts.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The result of the request.
+    pub fn result(&self) -> i32 {
+        self.result
+    }
+
+    /// Whether the step is a link timeout. Its result is `-ETIME` if it expired, and
+    /// `-ECANCELED` if the request it guards completed in time.
+    pub fn is_link_timeout(&self) -> bool {
+        self.link_timeout
+    }
+
+    /// Whether the request of the step was cancelled before it completed: because an earlier
+    /// step failed and severed the link, because its link timeout expired, or explicitly.
+    pub fn is_cancelled(&self) -> bool {
+        !self.link_timeout && self.result == -libc::ECANCELED
+    }
+}
@@ -1,12 +1,13 @@
 //! Completion Queue
 
 use std::fmt::{self, Debug};
-use std::mem;
 use std::mem::MaybeUninit;
+use std::os::unix::io::{FromRawFd, OwnedFd};
 use std::sync::atomic;
+use std::{io, mem};
 
 use crate::sys;
-use crate::util::{private, unsync_load, Mmap};
+use crate::util::{private, unsync_load, Region};
 
 pub(crate) struct Inner<E: EntryMarker> {
     head: *const atomic::AtomicU32,
@@ -131,14 +132,14 @@ fn test_user_data() {
 
 impl<E: EntryMarker> Inner<E> {
     #[rustfmt::skip]
-    pub(crate) unsafe fn new(cq_mmap: &Mmap, p: &sys::io_uring_params) -> Self {
-        let head         = cq_mmap.offset(p.cq_off.head         ) as *const atomic::AtomicU32;
-        let tail         = cq_mmap.offset(p.cq_off.tail         ) as *const atomic::AtomicU32;
-        let ring_mask    = cq_mmap.offset(p.cq_off.ring_mask    ).cast::<u32>().read();
-        let ring_entries = cq_mmap.offset(p.cq_off.ring_entries ).cast::<u32>().read();
-        let overflow     = cq_mmap.offset(p.cq_off.overflow     ) as *const atomic::AtomicU32;
-        let cqes         = cq_mmap.offset(p.cq_off.cqes         ) as *const E;
-        let flags        = cq_mmap.offset(p.cq_off.flags        ) as *const atomic::AtomicU32;
+    pub(crate) unsafe fn new(cq_ring: &Region, p: &sys::io_uring_params) -> Self {
+        let head         = cq_ring.offset(p.cq_off.head         ) as *const atomic::AtomicU32;
+        let tail         = cq_ring.offset(p.cq_off.tail         ) as *const atomic::AtomicU32;
+        let ring_mask    = cq_ring.offset(p.cq_off.ring_mask    ).cast::<u32>().read();
+        let ring_entries = cq_ring.offset(p.cq_off.ring_entries ).cast::<u32>().read();
+        let overflow     = cq_ring.offset(p.cq_off.overflow     ) as *const atomic::AtomicU32;
+        let cqes         = cq_ring.offset(p.cq_off.cqes         ) as *const E;
+        let flags        = cq_ring.offset(p.cq_off.flags        ) as *const atomic::AtomicU32;
 
         Self {
             head,
@@ -314,6 +315,57 @@ impl Entry {
     pub fn flags(&self) -> u32 {
         self.0.flags
     }
+
+    /// Returns the tag if this entry can be a resource tag completion, posted when the kernel
+    /// releases a file or buffer registered with a tag, such as through
+    /// [`register_files_tags`](crate::Submitter::register_files_tags).
+    ///
+    /// The kernel does not mark these entries: they carry the tag as their user data with a
+    /// result and flags of zero. To tell them apart from request completions, reserve the bits of
+    /// `marker` for tags, so that every tag has them set and no request user data does.
+    #[inline]
+    pub fn resource_tag(&self, marker: u64) -> Option<u64> {
+        let cqe = &self.0;
+        (cqe.user_data & marker == marker && cqe.res == 0 && cqe.flags == 0)
+            .then_some(cqe.user_data)
+    }
+
+    /// Decode the result of this entry as the result of the request `T`.
+    ///
+    /// ```
+    /// use io_uring::{cqueue, opcode};
+    ///
+    /// fn bytes_read(cqe: &cqueue::Entry) -> std::io::Result<usize> {
+    ///     cqe.decode::<opcode::Read>()
+    /// }
+    /// ```
+    #[inline]
+    pub fn decode<T: Decode>(&self) -> T::Output {
+        T::decode(self)
+    }
+
+    /// Decode the result of this entry as the outcome of a request submitted with
+    /// [`BUFFER_SELECT`](crate::squeue::Flags::BUFFER_SELECT), such as a
+    /// [`Read`](crate::opcode::Read) or a [`Recv`](crate::opcode::Recv), giving the buffer the
+    /// kernel picked along with the number of bytes it holds.
+    #[inline]
+    pub fn decode_selected(&self) -> io::Result<SelectedBuffer> {
+        decode_selected(self)
+    }
+
+    /// Decode the result of this entry as the file descriptor created by the request `T`, taking
+    /// ownership of it.
+    ///
+    /// # Safety
+    ///
+    /// This entry must be the completion of a `T` request that did not install a direct
+    /// descriptor through `file_index`, and the file descriptor must not be taken more than once,
+    /// such as by decoding a clone of this entry as well. Otherwise the result is not a file
+    /// descriptor owned by the caller, and it would be closed from under its actual owner.
+    #[inline]
+    pub unsafe fn decode_fd<T: DecodeFd>(&self) -> io::Result<OwnedFd> {
+        decode_res(self).map(|fd| OwnedFd::from_raw_fd(fd as _))
+    }
 }
 
 impl private::Sealed for Entry {}
@@ -380,6 +432,42 @@ impl Entry32 {
         self.0 .0.flags
     }
 
+    /// Returns the tag if this entry can be a resource tag completion.
+    ///
+    /// See [`Entry::resource_tag`] for details.
+    #[inline]
+    pub fn resource_tag(&self, marker: u64) -> Option<u64> {
+        self.0.resource_tag(marker)
+    }
+
+    /// Decode the result of this entry as the result of the request `T`.
+    ///
+    /// See [`Entry::decode`] for details.
+    #[inline]
+    pub fn decode<T: Decode>(&self) -> T::Output {
+        T::decode(&self.0)
+    }
+
+    /// Decode the result of this entry as the outcome of a request submitted with
+    /// [`BUFFER_SELECT`](crate::squeue::Flags::BUFFER_SELECT).
+    ///
+    /// See [`Entry::decode_selected`] for details.
+    #[inline]
+    pub fn decode_selected(&self) -> io::Result<SelectedBuffer> {
+        self.0.decode_selected()
+    }
+
+    /// Decode the result of this entry as the file descriptor created by the request `T`, taking
+    /// ownership of it.
+    ///
+    /// # Safety
+    ///
+    /// See [`Entry::decode_fd`].
+    #[inline]
+    pub unsafe fn decode_fd<T: DecodeFd>(&self) -> io::Result<OwnedFd> {
+        self.0.decode_fd::<T>()
+    }
+
     /// Additional data available in 32-byte completion queue entries (CQEs).
     #[inline]
     pub fn big_cqe(&self) -> &[u64; 2] {
@@ -476,3 +564,114 @@ pub fn sock_nonempty(flags: u32) -> bool {
 pub fn notif(flags: u32) -> bool {
     flags & sys::IORING_CQE_F_NOTIF != 0
 }
+
+/// Requests whose completion queue entries can be decoded into a typed result.
+///
+/// This is implemented by the [opcode](crate::opcode) types, so that a completion can be decoded
+/// through [`Entry::decode`] once the request it belongs to is known. The error of a failed
+/// request is always decoded as an [`io::Error`]; on success:
+///
+/// - requests transferring data, such as [`Read`](crate::opcode::Read) or
+///   [`Send`](crate::opcode::Send), give the number of bytes transferred;
+/// - multishot and bundle receives, which always pick their buffers from a provided buffer
+///   group, give a [`SelectedBuffer`], whose buffer id is `None` if no buffer was consumed;
+/// - [`GetSockOpt`](crate::opcode::GetSockOpt) gives the length of the option value;
+/// - [`AsyncCancel2`](crate::opcode::AsyncCancel2) gives the number of requests cancelled;
+/// - [`PollAdd`](crate::opcode::PollAdd) gives the mask of ready events;
+/// - requests with no result beyond success, such as [`Fsync`](crate::opcode::Fsync) or
+///   [`Connect`](crate::opcode::Connect), give `()`.
+///
+/// Requests creating a file descriptor, such as [`Accept`](crate::opcode::Accept), implement
+/// [`DecodeFd`] instead. Other requests submitted with
+/// [`BUFFER_SELECT`](crate::squeue::Flags::BUFFER_SELECT) are decoded with
+/// [`Entry::decode_selected`].
+pub trait Decode {
+    /// The decoded result of the request.
+    type Output;
+
+    /// Decode the result of `cqe`, a completion of this request.
+    fn decode(cqe: &Entry) -> Self::Output;
+}
+
+/// Requests whose completion carries a new file descriptor, such as
+/// [`Accept`](crate::opcode::Accept), [`OpenAt`](crate::opcode::OpenAt) or
+/// [`Socket`](crate::opcode::Socket), decoded into an [`OwnedFd`] with [`Entry::decode_fd`].
+///
+/// Requests that install a direct descriptor through `file_index` complete with the index of the
+/// slot, or with `0` if it was chosen by the caller, rather than with a file descriptor: their
+/// results must not be decoded this way.
+pub trait DecodeFd {}
+
+/// The outcome of a read or receive that picked its buffer from a provided buffer group,
+/// decoded from the completions of requests such as [`RecvMulti`](crate::opcode::RecvMulti), or
+/// with [`Entry::decode_selected`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SelectedBuffer {
+    buf_id: Option<u16>,
+    len: usize,
+}
+
+impl SelectedBuffer {
+    /// The ID of the buffer that holds the received data.
+    ///
+    /// This is `None` if no buffer was consumed, for example when the end of the stream was
+    /// reached. For bundle receives, the data continues into the buffers following this one.
+    #[inline]
+    pub fn buf_id(&self) -> Option<u16> {
+        self.buf_id
+    }
+
+    /// The number of bytes read or received.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no bytes were read or received.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[inline]
+fn decode_res(cqe: &Entry) -> io::Result<u32> {
+    if cqe.result() < 0 {
+        Err(io::Error::from_raw_os_error(-cqe.result()))
+    } else {
+        Ok(cqe.result() as u32)
+    }
+}
+
+#[inline]
+pub(crate) fn decode_unit(cqe: &Entry) -> io::Result<()> {
+    decode_res(cqe).map(drop)
+}
+
+#[inline]
+pub(crate) fn decode_len(cqe: &Entry) -> io::Result<usize> {
+    decode_res(cqe).map(|len| len as usize)
+}
+
+#[inline]
+pub(crate) fn decode_mask(cqe: &Entry) -> io::Result<u32> {
+    decode_res(cqe)
+}
+
+#[inline]
+pub(crate) fn decode_timeout(cqe: &Entry) -> io::Result<()> {
+    // An expired timeout is the expected outcome, not a failure.
+    if cqe.result() == -libc::ETIME {
+        Ok(())
+    } else {
+        decode_unit(cqe)
+    }
+}
+
+#[inline]
+pub(crate) fn decode_selected(cqe: &Entry) -> io::Result<SelectedBuffer> {
+    decode_len(cqe).map(|len| SelectedBuffer {
+        buf_id: buffer_select(cqe.flags()),
+        len,
+    })
+}
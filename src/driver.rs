@@ -0,0 +1,371 @@
+//! A minimal single-threaded driver turning requests into futures.
+//!
+//! This module is only available with the `driver` feature.
+//!
+//! A [`Driver`] owns an [`IoUring`] and submits requests on behalf of [`Op`] futures, which
+//! resolve to the completion queue entry of their request. [`Driver::block_on`] runs a future to
+//! completion, waiting on the ring whenever the future cannot make progress.
+//!
+//! ```no_run
+//! use io_uring::driver::Driver;
+//! use io_uring::{opcode, types};
+//! use std::os::unix::io::AsRawFd;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let driver = Driver::new(8)?;
+//! let file = std::fs::File::open("README.md")?;
+//!
+//! let mut buf = vec![0; 1024];
+//! let read_e = opcode::Read::new(types::Fd(file.as_raw_fd()), buf.as_mut_ptr(), buf.len() as _);
+//!
+//! // Safety: the buffer is moved into the driver along with the request.
+//! let (cqe, buf) = driver.block_on(unsafe { driver.submit(read_e.build(), buf) })?;
+//! let n = cqe.decode::<opcode::Read>()?;
+//! println!("{:?}", &buf[..n]);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::{fmt, io, thread};
+
+use crate::buf::OwnedOp;
+use crate::registry::{Completion, OpRegistry};
+use crate::{cqueue, opcode, squeue, IoUring};
+
+/// A ring along with the requests in flight on it.
+///
+/// Cloning a `Driver` is cheap and refers to the same ring.
+#[derive(Clone)]
+pub struct Driver {
+    inner: Rc<RefCell<Inner>>,
+}
+
+struct Inner {
+    ring: IoUring,
+    ops: OpRegistry<Rc<RefCell<State>>>,
+
+    // Requests that did not fit into the submission queue.
+    backlog: VecDeque<squeue::Entry>,
+}
+
+struct State {
+    cqe: Option<cqueue::Entry>,
+    waker: Option<Waker>,
+
+    // Whatever the request points to, kept until the request has completed.
+    data: Option<Box<dyn Any>>,
+}
+
+/// A future resolving to the completion queue entry of a request, along with the data that was
+/// submitted with it.
+///
+/// Dropping an `Op` before it has resolved cancels its request with an
+/// [`AsyncCancel`](opcode::AsyncCancel) request. The data is kept by the driver until the
+/// request has completed.
+#[must_use = "futures do nothing unless polled, and dropping an `Op` cancels its request"]
+pub struct Op<D: 'static> {
+    driver: Driver,
+    user_data: u64,
+    state: Rc<RefCell<State>>,
+    // `Op` never pins `D`, which is kept by the driver rather than the future.
+    marker: PhantomData<fn() -> D>,
+}
+
+impl Driver {
+    /// Create a driver with a new ring of `entries` entries.
+    pub fn new(entries: u32) -> io::Result<Driver> {
+        IoUring::new(entries).map(Driver::with_ring)
+    }
+
+    /// Create a driver on top of `ring`.
+    ///
+    /// The driver submits all requests and reaps all completions of the ring, which must not be
+    /// used otherwise.
+    pub fn with_ring(ring: IoUring) -> Driver {
+        Driver {
+            inner: Rc::new(RefCell::new(Inner {
+                ring,
+                ops: OpRegistry::new(),
+                backlog: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Submit `entry` and return a future resolving to its completion queue entry along with
+    /// `data`.
+    ///
+    /// The user data of `entry` is replaced by the driver. The request is only handed to the
+    /// kernel once the driver runs, through [`block_on`](Self::block_on).
+    ///
+    /// Multishot requests are not supported: the completions flagged with
+    /// [`more`](cqueue::more) are discarded, and the future only resolves with the final one.
+    ///
+    /// # Safety
+    ///
+    /// Developers must ensure that parameters of the entry (such as buffer) are valid until the
+    /// request has completed, for example by pointing into memory owned by `data`, which the
+    /// driver keeps until then even if the future is dropped. Memory inline in `data` moves along
+    /// with it, so it must be kept in a `Vec<u8>`, a `Box` or similar.
+    pub unsafe fn submit<D: 'static>(&self, entry: squeue::Entry, data: D) -> Op<D> {
+        let state = Rc::new(RefCell::new(State {
+            cqe: None,
+            waker: None,
+            data: Some(Box::new(data)),
+        }));
+        let user_data = self.inner.borrow_mut().push(entry, state.clone());
+
+        Op {
+            driver: self.clone(),
+            user_data,
+            state,
+            marker: PhantomData,
+        }
+    }
+
+    /// Submit a request owning its memory, and return a future resolving to its output.
+    ///
+    /// Unlike [`submit`](Self::submit), this is safe, as the request keeps the memory it refers to
+    /// alive until it completes.
+    ///
+    /// ```no_run
+    /// use io_uring::buf::BufOp;
+    /// use io_uring::driver::Driver;
+    /// use io_uring::types;
+    /// use std::os::unix::io::AsRawFd;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let driver = Driver::new(8)?;
+    /// let file = std::fs::File::open("README.md")?;
+    ///
+    /// let read = BufOp::read(types::Fd(file.as_raw_fd()), Vec::with_capacity(1024), 0);
+    /// let (res, buf) = driver.block_on(driver.submit_owned(read))?;
+    /// assert_eq!(res?, buf.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn submit_owned<T: OwnedOp + 'static>(&self, op: T) -> Owned<T> {
+        let entry = op.entry().clone();
+
+        // Safety: the request owns the memory its entry refers to, and is kept by the driver
+        // until the request has completed.
+        Owned {
+            op: unsafe { self.submit(entry, op) },
+        }
+    }
+
+    /// The number of requests submitted through the driver that have not completed yet,
+    /// including those cancelled by dropping their [`Op`].
+    pub fn in_flight(&self) -> usize {
+        self.inner.borrow().ops.len()
+    }
+
+    /// Run `future` to completion.
+    ///
+    /// Whenever the future cannot make progress, the driver submits the pending requests and
+    /// waits for at least one of them to complete. If no request is in flight, the thread is
+    /// parked until the future is woken instead.
+    ///
+    /// Fails if submitting to or waiting on the ring fails.
+    pub fn block_on<F: Future>(&self, future: F) -> io::Result<F::Output> {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return Ok(output);
+            }
+
+            if self.in_flight() == 0 {
+                thread::park();
+            } else {
+                self.turn()?;
+            }
+        }
+    }
+
+    /// Submit the pending requests, wait for at least one completion and wake the futures of the
+    /// requests that have completed.
+    fn turn(&self) -> io::Result<()> {
+        let done = {
+            let mut inner = self.inner.borrow_mut();
+            inner.flush();
+
+            match inner.ring.submit_and_wait(1) {
+                Ok(_) => (),
+                Err(ref err)
+                    if err.raw_os_error() == Some(libc::EINTR)
+                        || err.raw_os_error() == Some(libc::EBUSY) => {}
+                Err(err) => return Err(err),
+            }
+
+            inner.reap()
+        };
+
+        // Wake outside of the borrow, as waking may drop or poll other futures. Dropping the state
+        // also drops the data of requests whose future is gone, which may use the driver as well.
+        for state in done {
+            let waker = state.borrow_mut().waker.take();
+            drop(state);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Driver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Driver")
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
+
+impl Inner {
+    fn push(&mut self, entry: squeue::Entry, state: Rc<RefCell<State>>) -> u64 {
+        let user_data = self.ops.insert(state);
+        self.backlog.push_back(entry.user_data(user_data));
+        self.flush();
+        user_data
+    }
+
+    /// Move as much of the backlog into the submission queue as fits.
+    fn flush(&mut self) {
+        while let Some(entry) = self.backlog.front() {
+            // Safety: the data of the request is kept in the registry until it has completed.
+            if unsafe { self.ring.submission().push(entry) }.is_err() {
+                // Make room, the requests left behind are pushed on the next turn.
+                match self.ring.submit() {
+                    Ok(n) if n > 0 => continue,
+                    _ => break,
+                }
+            }
+            self.backlog.pop_front();
+        }
+    }
+
+    /// Hand the completions to the states of their requests, returning the states of the
+    /// requests that have completed.
+    fn reap(&mut self) -> Vec<Rc<RefCell<State>>> {
+        let mut done = Vec::new();
+
+        for cqe in self.ring.completion() {
+            if let Some(Completion::Done(state)) = self.ops.complete(&cqe) {
+                state.borrow_mut().cqe = Some(cqe);
+                done.push(state);
+            }
+        }
+
+        done
+    }
+}
+
+impl<D: 'static> Op<D> {
+    /// The user data the request was submitted with.
+    pub fn user_data(&self) -> u64 {
+        self.user_data
+    }
+}
+
+impl<D: 'static> Future for Op<D> {
+    type Output = (cqueue::Entry, D);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.borrow_mut();
+
+        match state.cqe.take() {
+            Some(cqe) => {
+                let data = state.data.take().expect("`Op` polled after completion");
+                let data = *data.downcast::<D>().unwrap_or_else(|_| unreachable!());
+                Poll::Ready((cqe, data))
+            }
+            None => {
+                match state.waker {
+                    Some(ref waker) if waker.will_wake(cx.waker()) => (),
+                    _ => state.waker = Some(cx.waker().clone()),
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<D: 'static> fmt::Debug for Op<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Op")
+            .field("user_data", &self.user_data)
+            .finish()
+    }
+}
+
+impl<D: 'static> Drop for Op<D> {
+    fn drop(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.waker = None;
+
+        // Nothing to cancel once the request has completed.
+        if state.cqe.is_some() || state.data.is_none() {
+            return;
+        }
+        drop(state);
+
+        // The cancellation is a request of its own, which nobody waits for.
+        let cancel = Rc::new(RefCell::new(State {
+            cqe: None,
+            waker: None,
+            data: None,
+        }));
+        self.driver
+            .inner
+            .borrow_mut()
+            .push(opcode::AsyncCancel::new(self.user_data).build(), cancel);
+    }
+}
+
+/// A future resolving to the output of a request owning its memory, returned by
+/// [`Driver::submit_owned`].
+///
+/// Dropping an `Owned` before it has resolved cancels its request, like dropping an [`Op`].
+#[must_use = "futures do nothing unless polled, and dropping an `Owned` cancels its request"]
+pub struct Owned<T: 'static> {
+    op: Op<T>,
+}
+
+impl<T: OwnedOp + 'static> Future for Owned<T> {
+    type Output = T::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.op)
+            .poll(cx)
+            // Safety: the driver resolved the future with the completion of the request.
+            .map(|(cqe, op)| unsafe { op.complete(&cqe) })
+    }
+}
+
+impl<T: 'static> fmt::Debug for Owned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Owned")
+            .field("user_data", &self.op.user_data)
+            .finish()
+    }
+}
+
+struct ThreadWaker(thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
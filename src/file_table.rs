@@ -0,0 +1,244 @@
+//! Registered file tables.
+//!
+//! A [`FixedFileTable`] registers a sparse file table with the kernel and keeps track of which of
+//! its slots are in use. Every slot handed out is owned by a [`FixedFile`], which can be passed
+//! wherever a [`Fixed`] file is accepted and empties the slot when dropped, so that slots are
+//! neither leaked nor reused while still in use.
+
+use std::cell::RefCell;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::rc::Rc;
+use std::{fmt, io, mem, ptr};
+
+use crate::register::{execute, RegisterRing};
+use crate::types::{sealed, DestinationSlot, Fixed};
+use crate::util::{cast_ptr, OwnedFd};
+use crate::{opcode, squeue, sys, Submitter};
+
+/// A registered file table whose slots are allocated by this library.
+///
+/// The slots of the table must only be filled through [`FixedFile`]s taken from it: requests
+/// using [`DestinationSlot::auto_target`] or
+/// [`register_files_update`](Submitter::register_files_update) would fill slots that the table
+/// considers free. Use [`FixedFile::dest`] to have a request install a file into a slot instead.
+///
+/// The table is unregistered once the `FixedFileTable` and all the [`FixedFile`]s taken from it
+/// have been dropped.
+///
+/// Cloning a `FixedFileTable` is cheap and refers to the same table.
+#[derive(Clone)]
+pub struct FixedFileTable {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    nr: u32,
+    free: RefCell<Vec<u32>>,
+
+    // A duplicate of the ring file descriptor, so that slots can be emptied and the table
+    // unregistered even if the `IoUring` has already been dropped.
+    fd: OwnedFd,
+}
+
+impl FixedFileTable {
+    /// Register a sparse file table of `nr` slots with the ring of `submitter`.
+    ///
+    /// The ring must not have a file table registered already.
+    pub fn new(submitter: &Submitter<'_>, nr: u32) -> io::Result<FixedFileTable> {
+        let fd = submitter.dup_fd()?;
+
+        submitter.register_files_sparse(nr)?;
+
+        Ok(FixedFileTable {
+            inner: Rc::new(Inner {
+                nr,
+                // Hand out the lowest slots first.
+                free: RefCell::new((0..nr).rev().collect()),
+                fd,
+            }),
+        })
+    }
+
+    /// The number of slots in the table.
+    pub fn capacity(&self) -> u32 {
+        self.inner.nr
+    }
+
+    /// The number of slots not owned by a [`FixedFile`].
+    pub fn available(&self) -> u32 {
+        self.inner.free.borrow().len() as u32
+    }
+
+    /// Install `fd` into a free slot.
+    ///
+    /// The kernel keeps its own reference to the file, so `fd` may be closed afterwards.
+    ///
+    /// Fails with `ENFILE` if there is no free slot.
+    pub fn insert(&self, fd: RawFd) -> io::Result<FixedFile> {
+        let file = self.reserve()?;
+        self.inner.update(file.slot, fd)?;
+        Ok(file)
+    }
+
+    /// Take a free slot without installing a file into it, so that a request can do so through
+    /// [`FixedFile::dest`].
+    ///
+    /// Fails with `ENFILE` if there is no free slot.
+    pub fn reserve(&self) -> io::Result<FixedFile> {
+        let slot = self
+            .inner
+            .free
+            .borrow_mut()
+            .pop()
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ENFILE))?;
+
+        Ok(FixedFile {
+            table: self.inner.clone(),
+            slot,
+        })
+    }
+}
+
+impl fmt::Debug for FixedFileTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FixedFileTable")
+            .field("capacity", &self.capacity())
+            .field("available", &self.available())
+            .finish()
+    }
+}
+
+impl Inner {
+    fn update(&self, slot: u32, fd: RawFd) -> io::Result<()> {
+        let fu = sys::io_uring_files_update {
+            offset: slot,
+            resv: 0,
+            fds: &fd as *const RawFd as _,
+        };
+        execute(
+            RegisterRing::RawFd(self.fd.as_raw_fd()),
+            sys::IORING_REGISTER_FILES_UPDATE,
+            cast_ptr::<sys::io_uring_files_update>(&fu).cast(),
+            1,
+        )
+        .map(drop)
+    }
+
+    fn release(&self, slot: u32) {
+        self.free.borrow_mut().push(slot);
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // Nothing can be done about a failure here, the table is gone along with the ring anyway.
+        let _ = execute(
+            RegisterRing::RawFd(self.fd.as_raw_fd()),
+            sys::IORING_UNREGISTER_FILES,
+            ptr::null(),
+            0,
+        );
+    }
+}
+
+/// A slot of a [`FixedFileTable`], which is emptied and handed back to the table when dropped.
+///
+/// `&FixedFile` can be used wherever a [`Fixed`] file is accepted. Requests using the slot must
+/// have completed before it is dropped, or they may end up using a file installed into the slot
+/// later on.
+pub struct FixedFile {
+    table: Rc<Inner>,
+    slot: u32,
+}
+
+impl FixedFile {
+    /// The index of the slot in the table.
+    pub fn slot(&self) -> u32 {
+        self.slot
+    }
+
+    /// The slot as a [`Fixed`] file, for requests that only accept that.
+    pub fn fixed(&self) -> Fixed {
+        Fixed(self.slot)
+    }
+
+    /// The slot as the destination of a request that installs a direct descriptor, such as
+    /// [`OpenAt`](opcode::OpenAt), [`Socket`](opcode::Socket) or [`Accept`](opcode::Accept).
+    pub fn dest(&self) -> DestinationSlot {
+        DestinationSlot::try_from_slot_target(self.slot).expect("slot out of range")
+    }
+
+    /// Close the file with a [`Close`](opcode::Close) request.
+    ///
+    /// The slot is handed back to the table once [`Closing::complete`] is called with the
+    /// result of the request.
+    pub fn close(self) -> Closing {
+        let this = mem::ManuallyDrop::new(self);
+        Closing {
+            // Safety: `this` is never dropped, so the table is moved out exactly once.
+            table: unsafe { ptr::read(&this.table) },
+            slot: this.slot,
+        }
+    }
+}
+
+impl sealed::UseFixed for &FixedFile {
+    #[inline]
+    fn into(self) -> sealed::Target {
+        sealed::Target::Fixed(self.slot)
+    }
+}
+
+impl fmt::Debug for FixedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FixedFile")
+            .field("slot", &self.slot)
+            .finish()
+    }
+}
+
+impl Drop for FixedFile {
+    fn drop(&mut self) {
+        // The slot can be reused even if this fails, the next file installed replaces it anyway.
+        let _ = self.table.update(self.slot, -1);
+        self.table.release(self.slot);
+    }
+}
+
+/// A [`FixedFile`] being closed by a request.
+///
+/// If this is dropped without calling [`complete`](Self::complete), the slot is never handed
+/// back to the table, as the request may still close whatever file is installed into it later.
+pub struct Closing {
+    table: Rc<Inner>,
+    slot: u32,
+}
+
+impl Closing {
+    /// The index of the slot being closed.
+    pub fn slot(&self) -> u32 {
+        self.slot
+    }
+
+    /// Build the [`Close`](opcode::Close) request.
+    pub fn entry(&self) -> squeue::Entry {
+        opcode::Close::new(Fixed(self.slot)).build()
+    }
+
+    /// Hand the slot back to the table once the request has completed with `result`.
+    ///
+    /// If the request failed, for example because it was cancelled, the slot is emptied
+    /// synchronously instead.
+    pub fn complete(self, result: i32) {
+        if result < 0 {
+            let _ = self.table.update(self.slot, -1);
+        }
+        self.table.release(self.slot);
+    }
+}
+
+impl fmt::Debug for Closing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Closing").field("slot", &self.slot).finish()
+    }
+}
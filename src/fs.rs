@@ -0,0 +1,381 @@
+//! Files whose I/O goes through a [`Driver`].
+//!
+//! This module is only available with the `driver` feature.
+
+use std::cell::Cell;
+use std::ffi::CString;
+use std::future::Future;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::path::Path;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::{fmt, io};
+
+use crate::buf::{BufOp, IoBuf, IoBufMut, OwnedOp};
+use crate::driver::Driver;
+use crate::file_table::FixedFile;
+use crate::types::{self, sealed, FsyncFlags, OpenHow};
+use crate::util::OwnedFd;
+use crate::{cqueue, opcode, squeue};
+
+/// An open file whose requests are submitted through a [`Driver`].
+///
+/// The file is either a regular file descriptor, or a direct descriptor in a slot of a
+/// [`FixedFileTable`](crate::file_table::FixedFileTable). Every request holds on to it until it
+/// has completed, even if its future is dropped, so that the descriptor it refers to stays open.
+///
+/// Use [`close`](Self::close) to close the file through the ring. Dropping it instead closes the
+/// file descriptor or empties the slot synchronously, once the last request using it has
+/// completed.
+///
+/// ```no_run
+/// use io_uring::driver::Driver;
+/// use io_uring::fs::File;
+/// use io_uring::types::OpenHow;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let driver = Driver::new(8)?;
+///
+/// driver.block_on(async {
+///     let how = OpenHow::new().flags(libc::O_RDONLY as _);
+///     let file = File::open(&driver, "README.md", &how).await?;
+///
+///     let (res, buf) = file.read_at(Vec::with_capacity(1024), 0).await;
+///     println!("{:?}", &buf[..res?]);
+///
+///     file.close().await
+/// })?
+/// # }
+/// ```
+pub struct File {
+    driver: Driver,
+    fd: Rc<SharedFd>,
+}
+
+/// The descriptor of a [`File`], shared with the requests using it.
+struct SharedFd {
+    handle: Handle,
+    /// The task closing the file, waiting for the requests to let go of it.
+    closing: Cell<Option<Waker>>,
+}
+
+enum Handle {
+    Fd(OwnedFd),
+    Fixed(FixedFile),
+}
+
+impl File {
+    /// Open the file at `path` as specified by `how`, with an [`OpenAt2`](opcode::OpenAt2).
+    ///
+    /// A relative `path` is resolved from the current working directory.
+    pub async fn open(driver: &Driver, path: impl AsRef<Path>, how: &OpenHow) -> io::Result<File> {
+        let (path, how) = (cstring(path.as_ref())?, Box::new(*how));
+        let open_e = opcode::OpenAt2::new(types::Fd(libc::AT_FDCWD), path.as_ptr(), &*how);
+
+        // Safety: the path and `how` are kept by the driver until the request has completed.
+        let (cqe, _) = unsafe { driver.submit(open_e.build(), (path, how)) }.await;
+        // Safety: `cqe` is the only completion of the open, which has no `file_index`.
+        let fd = unsafe { cqe.decode_fd::<opcode::OpenAt2>() }?;
+
+        Ok(File::new(
+            driver,
+            Handle::Fd(unsafe { OwnedFd::from_raw_fd(fd.into_raw_fd()) }),
+        ))
+    }
+
+    /// Open the file at `path` as specified by `how` into the slot of `file`, with an
+    /// [`OpenAt2`](opcode::OpenAt2).
+    ///
+    /// The slot must belong to the file table of the ring of `driver`.
+    pub async fn open_fixed(
+        driver: &Driver,
+        path: impl AsRef<Path>,
+        how: &OpenHow,
+        file: FixedFile,
+    ) -> io::Result<File> {
+        let (path, how) = (cstring(path.as_ref())?, Box::new(*how));
+        let open_e = opcode::OpenAt2::new(types::Fd(libc::AT_FDCWD), path.as_ptr(), &*how)
+            .file_index(Some(file.dest()));
+
+        // Safety: the path and `how` are kept by the driver until the request has completed.
+        let (cqe, _) = unsafe { driver.submit(open_e.build(), (path, how)) }.await;
+        cqueue::decode_unit(&cqe)?;
+
+        Ok(File::from_fixed(driver, file))
+    }
+
+    /// Take over `file`.
+    pub fn from_std(driver: &Driver, file: std::fs::File) -> File {
+        File::new(
+            driver,
+            Handle::Fd(unsafe { OwnedFd::from_raw_fd(file.into_raw_fd()) }),
+        )
+    }
+
+    /// Take over the file installed in the slot of `file`.
+    ///
+    /// The slot must belong to the file table of the ring of `driver`.
+    pub fn from_fixed(driver: &Driver, file: FixedFile) -> File {
+        File::new(driver, Handle::Fixed(file))
+    }
+
+    fn new(driver: &Driver, handle: Handle) -> File {
+        File {
+            driver: driver.clone(),
+            fd: Rc::new(SharedFd {
+                handle,
+                closing: Cell::new(None),
+            }),
+        }
+    }
+
+    /// Read into `buf` from `pos`, with a single request.
+    ///
+    /// Returns the number of bytes read, which may be fewer than the size of `buf`, along with
+    /// the buffer.
+    pub async fn read_at<B: IoBufMut>(&self, buf: B, pos: u64) -> (io::Result<usize>, B) {
+        let read = BufOp::read(self.target(), buf, pos);
+        self.driver.submit_owned(self.hold(read)).await
+    }
+
+    /// Fill `buf` from `pos`, reading again after short reads.
+    ///
+    /// Fails with [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) if the end of the file is
+    /// reached first, in which case the data read so far is in the buffer.
+    pub async fn read_exact_at<B: IoBufMut>(&self, buf: B, pos: u64) -> (io::Result<()>, B) {
+        let total = buf.bytes_total();
+        let mut filled = 0;
+        let mut buf = buf;
+
+        while filled < total {
+            let (res, slice) = self.read_at(buf.slice(filled..), pos + filled as u64).await;
+            buf = slice.into_inner();
+
+            match res {
+                Ok(0) => {
+                    let err =
+                        io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer");
+                    return (Err(err), buf);
+                }
+                Ok(n) => filled += n,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return (Err(err), buf),
+            }
+        }
+
+        (Ok(()), buf)
+    }
+
+    /// Write the initialized bytes of `buf` at `pos`, with a single request.
+    ///
+    /// Returns the number of bytes written, which may be fewer than those of `buf`, along with
+    /// the buffer.
+    pub async fn write_at<B: IoBuf>(&self, buf: B, pos: u64) -> (io::Result<usize>, B) {
+        let write = BufOp::write(self.target(), buf, pos);
+        self.driver.submit_owned(self.hold(write)).await
+    }
+
+    /// Write all the initialized bytes of `buf` at `pos`, writing again after short writes.
+    pub async fn write_all_at<B: IoBuf>(&self, buf: B, pos: u64) -> (io::Result<()>, B) {
+        let total = buf.bytes_init();
+        let mut written = 0;
+        let mut buf = buf;
+
+        while written < total {
+            let (res, slice) = self
+                .write_at(buf.slice(written..total), pos + written as u64)
+                .await;
+            buf = slice.into_inner();
+
+            match res {
+                Ok(0) => {
+                    let err =
+                        io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer");
+                    return (Err(err), buf);
+                }
+                Ok(n) => written += n,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return (Err(err), buf),
+            }
+        }
+
+        (Ok(()), buf)
+    }
+
+    /// Flush the data and metadata of the file to the device, with an
+    /// [`Fsync`](opcode::Fsync).
+    pub async fn sync_all(&self) -> io::Result<()> {
+        self.submit(opcode::Fsync::new(self.target()).build()).await
+    }
+
+    /// Flush the data of the file to the device, along with the metadata needed to read it back,
+    /// with an [`Fsync`](opcode::Fsync) using [`FsyncFlags::DATASYNC`].
+    pub async fn sync_data(&self) -> io::Result<()> {
+        let fsync_e = opcode::Fsync::new(self.target()).flags(FsyncFlags::DATASYNC);
+        self.submit(fsync_e.build()).await
+    }
+
+    /// Allocate the disk space for `len` bytes from `offset`, growing the file if needed, with a
+    /// [`Fallocate`](opcode::Fallocate).
+    pub async fn allocate(&self, offset: u64, len: u64) -> io::Result<()> {
+        let fallocate_e = opcode::Fallocate::new(self.target(), len).offset(offset);
+        self.submit(fallocate_e.build()).await
+    }
+
+    /// Truncate or extend the file to `size` bytes, with a [`Ftruncate`](opcode::Ftruncate).
+    pub async fn set_len(&self, size: u64) -> io::Result<()> {
+        self.submit(opcode::Ftruncate::new(self.target(), size).build())
+            .await
+    }
+
+    /// Query the metadata of the file, with a [`Statx`](opcode::Statx).
+    ///
+    /// The basic statistics and the creation time are requested, see `stx_mask` for those the
+    /// file system provided. `statx` does not take direct descriptors, so this fails with
+    /// [`Unsupported`](io::ErrorKind::Unsupported) for files opened into a slot.
+    #[cfg(target_env = "gnu")]
+    pub async fn metadata(&self) -> io::Result<libc::statx> {
+        let fd = match self.fd.handle {
+            Handle::Fd(ref fd) => std::os::unix::io::AsRawFd::as_raw_fd(fd),
+            Handle::Fixed(_) => return Err(io::Error::from(io::ErrorKind::Unsupported)),
+        };
+
+        let mut statx: Box<libc::statx> = Box::new(unsafe { std::mem::zeroed() });
+        let statx_e = opcode::Statx::new(
+            types::Fd(fd),
+            b"\0".as_ptr().cast(),
+            &mut *statx as *mut libc::statx as *mut types::statx,
+        )
+        .flags(libc::AT_EMPTY_PATH)
+        .mask(libc::STATX_BASIC_STATS | libc::STATX_BTIME);
+
+        // Safety: the buffer is kept by the driver until the request has completed.
+        let data = (statx, self.in_flight());
+        let (cqe, (statx, _)) = unsafe { self.driver.submit(statx_e.build(), data) }.await;
+        cqe.decode::<opcode::Statx>()?;
+
+        Ok(*statx)
+    }
+
+    /// Close the file through the ring, with a [`Close`](opcode::Close).
+    ///
+    /// This waits for the requests still using the file, such as those whose futures were
+    /// dropped and are being cancelled, to complete first. The slot of a file opened into one is
+    /// handed back to its table once the request has completed.
+    pub async fn close(self) -> io::Result<()> {
+        let File { driver, fd } = self;
+        let handle = Unshare(Some(fd)).await;
+
+        match handle {
+            Handle::Fd(fd) => {
+                let close_e = opcode::Close::new(types::Fd(fd.into_raw_fd()));
+                let (cqe, ()) = unsafe { driver.submit(close_e.build(), ()) }.await;
+                cqe.decode::<opcode::Close>()
+            }
+            Handle::Fixed(file) => {
+                let closing = file.close();
+                let (cqe, ()) = unsafe { driver.submit(closing.entry(), ()) }.await;
+                closing.complete(cqe.result());
+                cqe.decode::<opcode::Close>()
+            }
+        }
+    }
+
+    fn target(&self) -> sealed::Target {
+        match self.fd.handle {
+            Handle::Fd(ref fd) => sealed::Target::Fd(std::os::unix::io::AsRawFd::as_raw_fd(fd)),
+            Handle::Fixed(ref file) => sealed::Target::Fixed(file.slot()),
+        }
+    }
+
+    /// A hold on the descriptor, to be kept by a request using it until it has completed.
+    fn in_flight(&self) -> InFlight {
+        InFlight(self.fd.clone())
+    }
+
+    /// Have `op` hold on to the descriptor.
+    fn hold<T: OwnedOp>(&self, op: T) -> Holding<T> {
+        Holding {
+            op,
+            _fd: self.in_flight(),
+        }
+    }
+
+    /// Submit a request that refers to no memory.
+    async fn submit(&self, entry: squeue::Entry) -> io::Result<()> {
+        // Safety: there is nothing but the descriptor for the request to refer to.
+        let (cqe, _) = unsafe { self.driver.submit(entry, self.in_flight()) }.await;
+        cqueue::decode_unit(&cqe)
+    }
+}
+
+impl fmt::Debug for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("File");
+        match self.target() {
+            sealed::Target::Fd(fd) => d.field("fd", &fd),
+            sealed::Target::Fixed(slot) => d.field("slot", &slot),
+        };
+        d.finish()
+    }
+}
+
+/// A hold of a request on the descriptor of a [`File`].
+struct InFlight(Rc<SharedFd>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        // Wake the task closing the file once it is the only one left holding the descriptor.
+        if Rc::strong_count(&self.0) == 2 {
+            if let Some(waker) = self.0.closing.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// A request owning its memory, holding on to the descriptor it uses as well.
+struct Holding<T> {
+    op: T,
+    _fd: InFlight,
+}
+
+// Safety: the memory the entry refers to is owned by `op`.
+unsafe impl<T: OwnedOp> OwnedOp for Holding<T> {
+    type Output = T::Output;
+
+    fn entry(&self) -> &squeue::Entry {
+        self.op.entry()
+    }
+
+    unsafe fn complete(self, cqe: &cqueue::Entry) -> Self::Output {
+        self.op.complete(cqe)
+    }
+}
+
+/// Waits for the requests to let go of the descriptor, resolving to it.
+struct Unshare(Option<Rc<SharedFd>>);
+
+impl Future for Unshare {
+    type Output = Handle;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Handle> {
+        let fd = self.0.take().expect("`Unshare` polled after completion");
+
+        match Rc::try_unwrap(fd) {
+            Ok(fd) => Poll::Ready(fd.handle),
+            Err(fd) => {
+                fd.closing.set(Some(cx.waker().clone()));
+                self.0 = Some(fd);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a nul byte"))
+}
@@ -45,9 +45,21 @@
 
 #[macro_use]
 mod util;
+pub mod buf;
+pub mod buf_ring;
+pub mod chain;
 pub mod cqueue;
+#[cfg(feature = "driver")]
+pub mod driver;
+pub mod file_table;
+#[cfg(feature = "driver")]
+pub mod fs;
 pub mod opcode;
 pub mod register;
+pub mod registered_buffers;
+#[cfg(feature = "registry")]
+pub mod registry;
+pub mod send_zc;
 pub mod squeue;
 mod submit;
 mod sys;
@@ -56,7 +68,7 @@ pub mod types;
 use std::marker::PhantomData;
 use std::mem::ManuallyDrop;
 use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
-use std::{cmp, io, mem};
+use std::{cmp, io, mem, ptr};
 
 #[cfg(feature = "io_safety")]
 use std::os::unix::io::{AsFd, BorrowedFd};
@@ -66,7 +78,7 @@ pub use register::Probe;
 pub use squeue::SubmissionQueue;
 pub use submit::EnterFlags;
 pub use submit::Submitter;
-use util::{Mmap, OwnedFd};
+use util::{Mmap, OwnedFd, Region};
 
 /// IoUring instance
 ///
@@ -81,16 +93,70 @@ where
 {
     sq: squeue::Inner<S>,
     cq: cqueue::Inner<C>,
-    fd: OwnedFd,
+    fd: RingFd,
     params: Parameters,
     memory: ManuallyDrop<MemoryMap>,
+    dontfork: bool,
 }
 
 #[allow(dead_code)]
-struct MemoryMap {
-    sq_mmap: Mmap,
-    sqe_mmap: Mmap,
-    cq_mmap: Option<Mmap>,
+enum MemoryMap {
+    /// The rings were mapped from the ring file descriptor and are unmapped on drop.
+    Mapped {
+        sq_mmap: Mmap,
+        sqe_mmap: Mmap,
+        cq_mmap: Option<Mmap>,
+    },
+    /// The rings live in memory provided by the application with
+    /// [`Builder::setup_no_mmap`], which it keeps ownership of.
+    User { rings: Region, sqes: Region },
+}
+
+/// How the ring is referred to when entering it or registering with it.
+pub(crate) enum RingFd {
+    /// A regular file descriptor.
+    Fd(OwnedFd),
+    /// The index of the ring in the registered ring table of the task that created it, for rings
+    /// built with [`Builder::setup_registered_fd_only`]. The ring is destroyed once the index is
+    /// unregistered.
+    Registered(u32),
+}
+
+impl RingFd {
+    #[inline]
+    pub(crate) fn as_owned_fd(&self) -> Option<&OwnedFd> {
+        match self {
+            RingFd::Fd(fd) => Some(fd),
+            RingFd::Registered(_) => None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn register_ring(&self) -> register::RegisterRing {
+        match *self {
+            RingFd::Fd(ref fd) => register::RegisterRing::RawFd(fd.as_raw_fd()),
+            RingFd::Registered(index) => register::RegisterRing::RegisteredIndex(index as _),
+        }
+    }
+}
+
+impl Drop for RingFd {
+    fn drop(&mut self) {
+        if let RingFd::Registered(index) = *self {
+            let up = sys::io_uring_rsrc_update {
+                offset: index,
+                resv: 0,
+                data: 0,
+            };
+            // Nothing can be done about a failure here.
+            let _ = register::execute(
+                self.register_ring(),
+                sys::IORING_UNREGISTER_RING_FDS,
+                util::cast_ptr::<sys::io_uring_rsrc_update>(&up).cast(),
+                1,
+            );
+        }
+    }
 }
 
 /// IoUring build params
@@ -134,7 +200,7 @@ impl IoUring<squeue::Entry, cqueue::Entry> {
     /// `params` argument must be equivalent to the those previously filled in by the kernel when
     /// the provided ring was created.
     pub unsafe fn from_fd(fd: RawFd, params: Parameters) -> io::Result<Self> {
-        Self::with_fd_and_params(OwnedFd::from_raw_fd(fd), params.0)
+        Self::with_fd_and_params(RingFd::Fd(OwnedFd::from_raw_fd(fd)), params.0)
     }
 }
 
@@ -158,57 +224,16 @@ impl<S: squeue::EntryMarker, C: cqueue::EntryMarker> IoUring<S, C> {
     }
 
     fn with_params(entries: u32, mut p: sys::io_uring_params) -> io::Result<Self> {
-        let fd: OwnedFd = unsafe { OwnedFd::from_raw_fd(sys::io_uring_setup(entries, &mut p)?) };
+        let fd = unsafe { sys::io_uring_setup(entries, &mut p)? };
+        let fd = if p.flags & sys::IORING_SETUP_REGISTERED_FD_ONLY != 0 {
+            RingFd::Registered(fd as _)
+        } else {
+            RingFd::Fd(unsafe { OwnedFd::from_raw_fd(fd) })
+        };
         unsafe { Self::with_fd_and_params(fd, p) }
     }
 
-    unsafe fn with_fd_and_params(fd: OwnedFd, p: sys::io_uring_params) -> io::Result<Self> {
-        // NOTE: The `SubmissionQueue` and `CompletionQueue` are references,
-        // and their lifetime can never exceed `MemoryMap`.
-        //
-        // The memory mapped regions of `MemoryMap` never move,
-        // so `SubmissionQueue` and `CompletionQueue` are `Unpin`.
-        //
-        // I really hope that Rust can safely use self-reference types.
-        #[inline]
-        unsafe fn setup_queue<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
-            fd: &OwnedFd,
-            p: &sys::io_uring_params,
-        ) -> io::Result<(MemoryMap, squeue::Inner<S>, cqueue::Inner<C>)> {
-            let sq_len = p.sq_off.array as usize + p.sq_entries as usize * mem::size_of::<u32>();
-            let cq_len = p.cq_off.cqes as usize + p.cq_entries as usize * mem::size_of::<C>();
-            let sqe_len = p.sq_entries as usize * mem::size_of::<S>();
-            let sqe_mmap = Mmap::new(fd, sys::IORING_OFF_SQES as _, sqe_len)?;
-
-            if p.features & sys::IORING_FEAT_SINGLE_MMAP != 0 {
-                let scq_mmap =
-                    Mmap::new(fd, sys::IORING_OFF_SQ_RING as _, cmp::max(sq_len, cq_len))?;
-
-                let sq = squeue::Inner::new(&scq_mmap, &sqe_mmap, p);
-                let cq = cqueue::Inner::new(&scq_mmap, p);
-                let mm = MemoryMap {
-                    sq_mmap: scq_mmap,
-                    cq_mmap: None,
-                    sqe_mmap,
-                };
-
-                Ok((mm, sq, cq))
-            } else {
-                let sq_mmap = Mmap::new(fd, sys::IORING_OFF_SQ_RING as _, sq_len)?;
-                let cq_mmap = Mmap::new(fd, sys::IORING_OFF_CQ_RING as _, cq_len)?;
-
-                let sq = squeue::Inner::new(&sq_mmap, &sqe_mmap, p);
-                let cq = cqueue::Inner::new(&cq_mmap, p);
-                let mm = MemoryMap {
-                    cq_mmap: Some(cq_mmap),
-                    sq_mmap,
-                    sqe_mmap,
-                };
-
-                Ok((mm, sq, cq))
-            }
-        }
-
+    unsafe fn with_fd_and_params(fd: RingFd, p: sys::io_uring_params) -> io::Result<Self> {
         let (mm, sq, cq) = unsafe { setup_queue(&fd, &p)? };
 
         Ok(IoUring {
@@ -217,9 +242,73 @@ impl<S: squeue::EntryMarker, C: cqueue::EntryMarker> IoUring<S, C> {
             fd,
             params: Parameters(p),
             memory: ManuallyDrop::new(mm),
+            dontfork: false,
         })
     }
 
+    /// Resize the submission and completion queues of this instance in place.
+    ///
+    /// Unlike building a new instance, this keeps the registered files, buffers and any other
+    /// state attached to the ring. Entries that are pending in either queue are carried over to
+    /// the new rings; if they don't fit, this fails with `EOVERFLOW` and the rings are left
+    /// untouched.
+    ///
+    /// As with [`Builder::build`] and [`Builder::setup_cqsize`], the sizes are rounded up to the
+    /// next power of two, and `cq_entries` must not be smaller than `sq_entries`.
+    ///
+    /// The instance must have been created with [`Builder::setup_defer_taskrun`] and
+    /// [`Builder::setup_no_sqarray`], otherwise this fails with `EINVAL`. The kernel does support
+    /// resizing rings that have a submission queue array, but leaves `sq_off.array` zeroed in
+    /// the parameters it returns for the new rings, as the offset is only reported when a ring is
+    /// created. Without it, the array of the new rings cannot be located to set up the identity
+    /// mapping that [`SubmissionQueue`] relies on. If the instance was also created with
+    /// [`Builder::setup_single_issuer`], this must be called from the submitter task.
+    ///
+    /// Rings placed in application memory with [`Builder::setup_no_mmap`] cannot be resized and
+    /// fail with `EINVAL`.
+    ///
+    /// Available since 6.13.
+    pub fn resize(&mut self, sq_entries: u32, cq_entries: u32) -> io::Result<()> {
+        let flags = self.params.0.flags;
+        if flags & sys::IORING_SETUP_NO_SQARRAY == 0 || flags & sys::IORING_SETUP_NO_MMAP != 0 {
+            return Err(io::Error::from_raw_os_error(libc::EINVAL));
+        }
+
+        let mut p = sys::io_uring_params {
+            sq_entries,
+            cq_entries,
+            flags: sys::IORING_SETUP_CQSIZE,
+            ..Default::default()
+        };
+        register::execute(
+            self.fd.register_ring(),
+            sys::IORING_REGISTER_RESIZE_RINGS,
+            (&mut p as *mut sys::io_uring_params).cast(),
+            1,
+        )?;
+
+        // The kernel fills in the sizes and offsets of the new rings, which are mapped at the same
+        // offsets as the old ones. The old mappings stay valid until they are unmapped, but no
+        // longer refer to the rings that the kernel uses.
+        let mut params = self.params.0;
+        params.sq_entries = p.sq_entries;
+        params.cq_entries = p.cq_entries;
+        params.sq_off = p.sq_off;
+        params.cq_off = p.cq_off;
+
+        let (mm, sq, cq) = unsafe { setup_queue(&self.fd, &params)? };
+        if self.dontfork {
+            mm.dontfork()?;
+        }
+
+        self.sq = sq;
+        self.cq = cq;
+        self.params = Parameters(params);
+        drop(mem::replace(&mut *self.memory, mm));
+
+        Ok(())
+    }
+
     /// Get the submitter of this io_uring instance, which can be used to submit submission queue
     /// events to the kernel for execution and to register files or buffers with it.
     #[inline]
@@ -239,6 +328,13 @@ impl<S: squeue::EntryMarker, C: cqueue::EntryMarker> IoUring<S, C> {
         &self.params
     }
 
+    /// Get the file descriptor of the ring, or `None` if it was built with
+    /// [`Builder::setup_registered_fd_only`] and only has a registered one.
+    #[inline]
+    pub fn try_as_raw_fd(&self) -> Option<RawFd> {
+        self.fd.as_owned_fd().map(AsRawFd::as_raw_fd)
+    }
+
     /// Initiate asynchronous I/O. See [`Submitter::submit`] for more details.
     #[inline]
     pub fn submit(&self) -> io::Result<usize> {
@@ -311,6 +407,97 @@ impl<S: squeue::EntryMarker, C: cqueue::EntryMarker> IoUring<S, C> {
     }
 }
 
+// NOTE: The `SubmissionQueue` and `CompletionQueue` are references,
+// and their lifetime can never exceed `MemoryMap`.
+//
+// The memory mapped regions of `MemoryMap` never move,
+// so `SubmissionQueue` and `CompletionQueue` are `Unpin`.
+//
+// I really hope that Rust can safely use self-reference types.
+#[inline]
+unsafe fn setup_queue<S: squeue::EntryMarker, C: cqueue::EntryMarker>(
+    fd: &RingFd,
+    p: &sys::io_uring_params,
+) -> io::Result<(MemoryMap, squeue::Inner<S>, cqueue::Inner<C>)> {
+    let sq_len = p.sq_off.array as usize + p.sq_entries as usize * mem::size_of::<u32>();
+    let cq_len = p.cq_off.cqes as usize + p.cq_entries as usize * mem::size_of::<C>();
+    let sqe_len = p.sq_entries as usize * mem::size_of::<S>();
+
+    if p.flags & sys::IORING_SETUP_NO_MMAP != 0 {
+        let rings = ptr::NonNull::new(p.cq_off.user_addr as *mut libc::c_void)
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::EINVAL))?;
+        let sqes = ptr::NonNull::new(p.sq_off.user_addr as *mut libc::c_void)
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::EINVAL))?;
+        let rings = Region::new(rings, cmp::max(sq_len, cq_len));
+        let sqes = Region::new(sqes, sqe_len);
+
+        let sq = squeue::Inner::new(&rings, &sqes, p);
+        let cq = cqueue::Inner::new(&rings, p);
+        let mm = MemoryMap::User { rings, sqes };
+
+        return Ok((mm, sq, cq));
+    }
+
+    // A ring without a file descriptor cannot be mapped, the kernel requires it to use
+    // `IORING_SETUP_NO_MMAP`.
+    let fd = fd
+        .as_owned_fd()
+        .ok_or_else(|| io::Error::from_raw_os_error(libc::EINVAL))?;
+
+    let sqe_mmap = Mmap::new(fd, sys::IORING_OFF_SQES as _, sqe_len)?;
+
+    if p.features & sys::IORING_FEAT_SINGLE_MMAP != 0 {
+        let scq_mmap = Mmap::new(fd, sys::IORING_OFF_SQ_RING as _, cmp::max(sq_len, cq_len))?;
+
+        let sq = squeue::Inner::new(&scq_mmap, &sqe_mmap, p);
+        let cq = cqueue::Inner::new(&scq_mmap, p);
+        let mm = MemoryMap::Mapped {
+            sq_mmap: scq_mmap,
+            cq_mmap: None,
+            sqe_mmap,
+        };
+
+        Ok((mm, sq, cq))
+    } else {
+        let sq_mmap = Mmap::new(fd, sys::IORING_OFF_SQ_RING as _, sq_len)?;
+        let cq_mmap = Mmap::new(fd, sys::IORING_OFF_CQ_RING as _, cq_len)?;
+
+        let sq = squeue::Inner::new(&sq_mmap, &sqe_mmap, p);
+        let cq = cqueue::Inner::new(&cq_mmap, p);
+        let mm = MemoryMap::Mapped {
+            cq_mmap: Some(cq_mmap),
+            sq_mmap,
+            sqe_mmap,
+        };
+
+        Ok((mm, sq, cq))
+    }
+}
+
+impl MemoryMap {
+    /// Do not make the rings accessible by child processes after a `fork`.
+    fn dontfork(&self) -> io::Result<()> {
+        match self {
+            MemoryMap::Mapped {
+                sq_mmap,
+                sqe_mmap,
+                cq_mmap,
+            } => {
+                sq_mmap.dontfork()?;
+                sqe_mmap.dontfork()?;
+                if let Some(cq_mmap) = cq_mmap.as_ref() {
+                    cq_mmap.dontfork()?;
+                }
+            }
+            MemoryMap::User { rings, sqes } => {
+                rings.dontfork()?;
+                sqes.dontfork()?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl<S: squeue::EntryMarker, C: cqueue::EntryMarker> Drop for IoUring<S, C> {
     fn drop(&mut self) {
         // Ensure that `MemoryMap` is released before `fd`.
@@ -336,6 +523,14 @@ impl<S: squeue::EntryMarker, C: cqueue::EntryMarker> Builder<S, C> {
         self
     }
 
+    /// Like [`setup_iopoll`](Self::setup_iopoll), but sleep for a while before starting to poll
+    /// for a completion. This trades a little latency for much less CPU usage. The kernel picks
+    /// how long to sleep for from the completion times it has seen so far. Available since 6.13.
+    pub fn setup_hybrid_iopoll(&mut self) -> &mut Self {
+        self.params.flags |= sys::IORING_SETUP_IOPOLL | sys::IORING_SETUP_HYBRID_IOPOLL;
+        self
+    }
+
     /// Use a kernel thread to perform submission queue polling. This allows your application to
     /// issue I/O without ever context switching into the kernel, however it does use up a lot more
     /// CPU. You should use it when you are expecting very large amounts of I/O.
@@ -471,17 +666,55 @@ impl<S: squeue::EntryMarker, C: cqueue::EntryMarker> Builder<S, C> {
         self
     }
 
+    /// Place the rings in memory provided by the application instead of mapping them from the
+    /// ring file descriptor. `rings` holds the submission and completion queue rings, and `sqes`
+    /// holds the submission queue entries.
+    ///
+    /// This allows backing the rings with huge pages, or packing several rings into a single huge
+    /// page, for more predictable TLB behaviour. Available since 6.5.
+    ///
+    /// # Safety
+    ///
+    /// Both addresses must be page aligned, and point to memory that is large enough for the
+    /// requested number of entries, see `io_uring_setup(2)` for the sizes the kernel expects. The
+    /// memory must not be used for anything else, and must stay valid until the [`IoUring`] is
+    /// dropped; it is not freed by this crate.
+    pub unsafe fn setup_no_mmap(
+        &mut self,
+        rings: *mut libc::c_void,
+        sqes: *mut libc::c_void,
+    ) -> &mut Self {
+        self.params.flags |= sys::IORING_SETUP_NO_MMAP;
+        self.params.cq_off.user_addr = rings as u64;
+        self.params.sq_off.user_addr = sqes as u64;
+        self
+    }
+
+    /// Do not create a file descriptor for the ring, only register it with the current task as if
+    /// by [`Submitter::register_ring_fd`]. Such a ring never shows up in the file descriptor table
+    /// of the process. This requires [`setup_no_mmap`](Self::setup_no_mmap), as there is no file
+    /// descriptor to map the rings from. Available since 6.5.
+    ///
+    /// The registered index is only valid in the thread that built the ring, so the ring must
+    /// only be used from that thread, including dropping it. It also cannot be handed to APIs
+    /// that need a file descriptor: [`IoUring::try_as_raw_fd`] returns `None`, the `AsRawFd` and
+    /// `AsFd` implementations of [`IoUring`] panic, and the types of this crate that need to
+    /// outlive the ring, such as
+    /// [`BufRing`](buf_ring::BufRing) and [`FixedFileTable`](file_table::FixedFileTable), fail
+    /// with `EBADF`.
+    pub fn setup_registered_fd_only(&mut self) -> &mut Self {
+        self.params.flags |= sys::IORING_SETUP_REGISTERED_FD_ONLY;
+        self
+    }
+
     /// Build an [IoUring], with the specified number of entries in the submission queue and
     /// completion queue unless [`setup_cqsize`](Self::setup_cqsize) has been called.
     pub fn build(&self, entries: u32) -> io::Result<IoUring<S, C>> {
-        let ring = IoUring::with_params(entries, self.params)?;
+        let mut ring = IoUring::with_params(entries, self.params)?;
 
         if self.dontfork {
-            ring.memory.sq_mmap.dontfork()?;
-            ring.memory.sqe_mmap.dontfork()?;
-            if let Some(cq_mmap) = ring.memory.cq_mmap.as_ref() {
-                cq_mmap.dontfork()?;
-            }
+            ring.memory.dontfork()?;
+            ring.dontfork = true;
         }
 
         Ok(ring)
@@ -500,6 +733,18 @@ impl Parameters {
         self.0.flags & sys::IORING_SETUP_IOPOLL != 0
     }
 
+    /// Whether polling for completion events sleeps for a while first. Enabled with
+    /// [`Builder::setup_hybrid_iopoll`].
+    pub fn is_setup_hybrid_iopoll(&self) -> bool {
+        self.0.flags & sys::IORING_SETUP_HYBRID_IOPOLL != 0
+    }
+
+    /// Whether the ring only has a registered file descriptor. Enabled with
+    /// [`Builder::setup_registered_fd_only`].
+    pub fn is_setup_registered_fd_only(&self) -> bool {
+        self.0.flags & sys::IORING_SETUP_REGISTERED_FD_ONLY != 0
+    }
+
     /// Whether the single issuer hint is enabled. Enabled with [`Builder::setup_single_issuer`].
     pub fn is_setup_single_issuer(&self) -> bool {
         self.0.flags & sys::IORING_SETUP_SINGLE_ISSUER != 0
@@ -670,6 +915,12 @@ impl Parameters {
         self.0.features & sys::IORING_FEAT_NO_IOWAIT != 0
     }
 
+    /// Whether the kernel supports attaching attributes to reads and writes, such as with
+    /// [`Read::attr_pi`](opcode::Read::attr_pi). Available since kernel 6.14.
+    pub fn is_feature_rw_attr(&self) -> bool {
+        self.0.features & sys::IORING_FEAT_RW_ATTR != 0
+    }
+
     /// The number of submission queue entries allocated.
     pub fn sq_entries(&self) -> u32 {
         self.0.sq_entries
@@ -707,15 +958,27 @@ impl std::fmt::Debug for Parameters {
     }
 }
 
+/// # Panics
+///
+/// Panics if the ring was built with [`Builder::setup_registered_fd_only`], as it has no file
+/// descriptor. Use [`IoUring::try_as_raw_fd`] if that is a possibility.
 impl<S: squeue::EntryMarker, C: cqueue::EntryMarker> AsRawFd for IoUring<S, C> {
     fn as_raw_fd(&self) -> RawFd {
-        self.fd.as_raw_fd()
+        self.try_as_raw_fd()
+            .expect("the ring only has a registered file descriptor")
     }
 }
 
+/// # Panics
+///
+/// Panics if the ring was built with [`Builder::setup_registered_fd_only`], as it has no file
+/// descriptor.
 #[cfg(feature = "io_safety")]
 impl<S: squeue::EntryMarker, C: cqueue::EntryMarker> AsFd for IoUring<S, C> {
     fn as_fd(&self) -> BorrowedFd<'_> {
-        self.fd.as_fd()
+        self.fd
+            .as_owned_fd()
+            .expect("the ring only has a registered file descriptor")
+            .as_fd()
     }
 }
@@ -3,9 +3,11 @@
 #![allow(clippy::new_without_default)]
 
 use std::convert::TryInto;
-use std::mem;
 use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::{io, mem, ptr};
 
+use crate::cqueue;
 use crate::squeue::Entry;
 use crate::squeue::Entry128;
 use crate::sys;
@@ -94,6 +96,16 @@ macro_rules! opcode {
     }
 }
 
+// Point the read or write at its protection information attribute, if any.
+#[inline(always)]
+fn set_attr_pi(sqe: &mut sys::io_uring_sqe, attr_pi: *const types::PiAttr) {
+    if !attr_pi.is_null() {
+        let attr = unsafe { sqe.__bindgen_anon_6.__bindgen_anon_2.as_mut() };
+        attr.attr_ptr = attr_pi as _;
+        attr.attr_type_mask = sys::IORING_RW_ATTR_FLAG_PI as _;
+    }
+}
+
 /// inline zeroed to improve codegen
 #[inline(always)]
 fn sqe_zeroed() -> sys::io_uring_sqe {
@@ -104,17 +116,53 @@ opcode! {
     /// Do not perform any I/O.
     ///
     /// This is useful for testing the performance of the io_uring implementation itself.
+    ///
+    /// The options make it useful for testing the handling of completions too: it can complete
+    /// with an arbitrary result, and look up a file or a fixed buffer the way other requests do,
+    /// failing if it is not valid. A negative result fails the request like any other error,
+    /// severing the [link](crate::squeue::Flags::IO_LINK) it is part of. The options are only
+    /// supported by recent kernels.
     #[derive(Debug)]
-    pub struct Nop { ;; }
+    pub struct Nop {
+        ;;
+        /// Complete with `result` instead of 0.
+        result: Option<i32> = None,
+        /// Look up the file `fd`, failing with `-EBADF` if it is not valid.
+        fd: Option<types::Fd> = None,
+        /// Look up the registered file `fixed_fd`, failing with `-EBADF` if it is not valid.
+        /// Takes precedence over `fd`.
+        fixed_fd: Option<types::Fixed> = None,
+        /// Look up the registered buffer `buf_index`, failing with `-EFAULT` if it is not valid.
+        buf_index: Option<u16> = None
+    }
 
     pub const CODE = sys::IORING_OP_NOP;
 
     pub fn build(self) -> Entry {
-        let Nop {} = self;
+        let Nop { result, fd, fixed_fd, buf_index } = self;
 
         let mut sqe = sqe_zeroed();
         sqe.opcode = Self::CODE;
         sqe.fd = -1;
+
+        let mut flags = 0;
+        if let Some(result) = result {
+            flags |= sys::IORING_NOP_INJECT_RESULT;
+            sqe.len = result as _;
+        }
+        if let Some(types::Fixed(idx)) = fixed_fd {
+            flags |= sys::IORING_NOP_FILE | sys::IORING_NOP_FIXED_FILE;
+            sqe.fd = idx as _;
+        } else if let Some(types::Fd(fd)) = fd {
+            flags |= sys::IORING_NOP_FILE;
+            sqe.fd = fd;
+        }
+        if let Some(buf_index) = buf_index {
+            flags |= sys::IORING_NOP_FIXED_BUFFER;
+            sqe.__bindgen_anon_4.buf_index = buf_index;
+        }
+        sqe.__bindgen_anon_3.nop_flags = flags;
+
         Entry(sqe)
     }
 }
@@ -262,7 +310,14 @@ opcode! {
         offset: u64 = 0,
         /// Specified for read operations, contains a bitwise OR of per-I/O flags, as described in
         /// the `preadv2(2)` man page.
-        rw_flags: i32 = 0
+        rw_flags: i32 = 0,
+        /// Attach T10 protection information to the request.
+        ///
+        /// The [`PiAttr`](types::PiAttr) itself is read when the request is submitted, but the
+        /// integrity metadata buffer it points to is used by the kernel until the request
+        /// completes. Like the data buffer, it must be valid for the entire duration of the
+        /// operation, otherwise it may cause memory problems.
+        attr_pi: *const types::PiAttr = ptr::null()
     }
 
     pub const CODE = sys::IORING_OP_READ_FIXED;
@@ -272,7 +327,7 @@ opcode! {
             fd,
             buf, len, offset,
             buf_index,
-            ioprio, rw_flags
+            ioprio, rw_flags, attr_pi
         } = self;
 
         let mut sqe = sqe_zeroed();
@@ -284,6 +339,7 @@ opcode! {
         sqe.__bindgen_anon_1.off = offset;
         sqe.__bindgen_anon_3.rw_flags = rw_flags as _;
         sqe.__bindgen_anon_4.buf_index = buf_index;
+        set_attr_pi(&mut sqe, attr_pi);
         Entry(sqe)
     }
 }
@@ -305,7 +361,14 @@ opcode! {
         offset: u64 = 0,
         /// Specified for write operations, contains a bitwise OR of per-I/O flags, as described in
         /// the `pwritev2(2)` man page.
-        rw_flags: i32 = 0
+        rw_flags: i32 = 0,
+        /// Attach T10 protection information to the request.
+        ///
+        /// The [`PiAttr`](types::PiAttr) itself is read when the request is submitted, but the
+        /// integrity metadata buffer it points to is used by the kernel until the request
+        /// completes. Like the data buffer, it must be valid for the entire duration of the
+        /// operation, otherwise it may cause memory problems.
+        attr_pi: *const types::PiAttr = ptr::null()
     }
 
     pub const CODE = sys::IORING_OP_WRITE_FIXED;
@@ -315,7 +378,7 @@ opcode! {
             fd,
             buf, len, offset,
             buf_index,
-            ioprio, rw_flags
+            ioprio, rw_flags, attr_pi
         } = self;
 
         let mut sqe = sqe_zeroed();
@@ -327,6 +390,7 @@ opcode! {
         sqe.__bindgen_anon_1.off = offset;
         sqe.__bindgen_anon_3.rw_flags = rw_flags as _;
         sqe.__bindgen_anon_4.buf_index = buf_index;
+        set_attr_pi(&mut sqe, attr_pi);
         Entry(sqe)
     }
 }
@@ -362,7 +426,7 @@ opcode! {
         sqe.opcode = Self::CODE;
         assign_fd!(sqe.fd = fd);
         if multi {
-            sqe.len = sys::IORING_POLL_ADD_MULTI;
+            sqe.len |= sys::IORING_POLL_ADD_MULTI;
         }
 
         #[cfg(target_endian = "little")] {
@@ -404,6 +468,62 @@ opcode! {
     }
 }
 
+opcode! {
+    /// Update an existing [poll](PollAdd) request in place, changing the events it waits for,
+    /// the user data its completions carry, or both.
+    ///
+    /// If found, the `result` method of the `cqueue::Entry` will return 0.
+    /// If not found, `result` will return `-libc::ENOENT`.
+    ///
+    /// Setting `flags` replaces the events the poll waits for, setting `new_user_data` replaces
+    /// the user data of its future completions. The poll keeps working in the mode it was added
+    /// with, whether single or [multi](PollAdd::multi) shot.
+    ///
+    /// Available since 5.13.
+    #[derive(Debug)]
+    pub struct PollUpdate {
+        old_user_data: { u64 },
+        ;;
+        /// The bits that may be set in `flags` are defined in `<poll.h>`,
+        /// and documented in `poll(2)`.
+        flags: Option<u32> = None,
+        new_user_data: Option<u64> = None
+    }
+
+    pub const CODE = sys::IORING_OP_POLL_REMOVE;
+
+    pub fn build(self) -> Entry {
+        let PollUpdate { old_user_data, flags, new_user_data } = self;
+
+        let mut sqe = sqe_zeroed();
+        sqe.opcode = Self::CODE;
+        sqe.fd = -1;
+        sqe.__bindgen_anon_2.addr = old_user_data;
+
+        if let Some(flags) = flags {
+            sqe.len |= sys::IORING_POLL_UPDATE_EVENTS;
+
+            #[cfg(target_endian = "little")] {
+                sqe.__bindgen_anon_3.poll32_events = flags;
+            }
+
+            #[cfg(target_endian = "big")] {
+                let x = flags << 16;
+                let y = flags >> 16;
+                let flags = x | y;
+                sqe.__bindgen_anon_3.poll32_events = flags;
+            }
+        }
+
+        if let Some(user_data) = new_user_data {
+            sqe.len |= sys::IORING_POLL_UPDATE_USER_DATA;
+            sqe.__bindgen_anon_1.off = user_data;
+        }
+
+        Entry(sqe)
+    }
+}
+
 opcode! {
     /// Sync a file segment with disk, equivalent to `sync_file_range(2)`.
     #[derive(Debug)]
@@ -661,6 +781,18 @@ opcode! {
     }
 }
 
+impl Accept {
+    /// Accept a connection, storing the address of the peer in `addr`.
+    ///
+    /// `addr` must not be dropped until the request has completed, and should only be read
+    /// after that.
+    #[inline]
+    pub fn with_addr(fd: impl sealed::UseFixed, mut addr: Pin<&mut types::SockAddr>) -> Self {
+        let addrlen = addr.as_mut().len_mut_ptr();
+        Accept::new(fd, addr.as_mut_ptr(), addrlen)
+    }
+}
+
 opcode! {
     /// Set a socket option.
     pub struct SetSockOpt {
@@ -691,6 +823,84 @@ opcode! {
     }
 }
 
+opcode! {
+    /// Get a socket option.
+    ///
+    /// `optlen` is the size of the buffer at `optval`. On success, the `result` method of the
+    /// `cqueue::Entry` returns the size of the option value written to it. Only options of the
+    /// `SOL_SOCKET` level are supported.
+    pub struct GetSockOpt {
+        fd: { impl sealed::UseFixed },
+        level: { u32 },
+        optname: { u32 },
+        optval: { *mut libc::c_void },
+        optlen: { u32 },
+        ;;
+        flags: u32 = 0
+    }
+
+    pub const CODE = sys::IORING_OP_URING_CMD;
+
+    pub fn build(self) -> Entry {
+        let GetSockOpt { fd, level, optname, optval, optlen, flags } = self;
+        let mut sqe = sqe_zeroed();
+        sqe.opcode = Self::CODE;
+        assign_fd!(sqe.fd = fd);
+        sqe.__bindgen_anon_1.__bindgen_anon_1.cmd_op = sys::SOCKET_URING_OP_GETSOCKOPT;
+
+        sqe.__bindgen_anon_2.__bindgen_anon_1.level = level;
+        sqe.__bindgen_anon_2.__bindgen_anon_1.optname = optname;
+        sqe.__bindgen_anon_3.uring_cmd_flags = flags;
+        sqe.__bindgen_anon_5.optlen = optlen;
+        unsafe { *sqe.__bindgen_anon_6.optval.as_mut() = optval as u64 };
+        Entry(sqe)
+    }
+}
+
+opcode! {
+    /// Get the number of bytes in a socket's receive queue, equivalent to `ioctl(2)` with
+    /// `SIOCINQ`.
+    ///
+    /// On success, the `result` method of the `cqueue::Entry` returns the number of bytes.
+    pub struct SiocInq {
+        fd: { impl sealed::UseFixed },
+        ;;
+    }
+
+    pub const CODE = sys::IORING_OP_URING_CMD;
+
+    pub fn build(self) -> Entry {
+        let SiocInq { fd } = self;
+        let mut sqe = sqe_zeroed();
+        sqe.opcode = Self::CODE;
+        assign_fd!(sqe.fd = fd);
+        sqe.__bindgen_anon_1.__bindgen_anon_1.cmd_op = sys::SOCKET_URING_OP_SIOCINQ;
+        Entry(sqe)
+    }
+}
+
+opcode! {
+    /// Get the number of bytes in a socket's send queue that have not been acknowledged yet,
+    /// equivalent to `ioctl(2)` with `SIOCOUTQ`.
+    ///
+    /// On success, the `result` method of the `cqueue::Entry` returns the number of bytes.
+    pub struct SiocOutQ {
+        fd: { impl sealed::UseFixed },
+        ;;
+    }
+
+    pub const CODE = sys::IORING_OP_URING_CMD;
+
+    pub fn build(self) -> Entry {
+        let SiocOutQ { fd } = self;
+        let mut sqe = sqe_zeroed();
+        sqe.opcode = Self::CODE;
+        assign_fd!(sqe.fd = fd);
+        sqe.__bindgen_anon_1.__bindgen_anon_1.cmd_op = sys::SOCKET_URING_OP_SIOCOUTQ;
+        Entry(sqe)
+    }
+}
+
 opcode! {
     /// Attempt to cancel an already issued request.
     pub struct AsyncCancel {
@@ -761,6 +971,16 @@ opcode! {
     }
 }
 
+impl Connect {
+    /// Connect a socket to `addr`.
+    ///
+    /// `addr` must not be dropped until the request has completed.
+    #[inline]
+    pub fn with_addr(fd: impl sealed::UseFixed, addr: Pin<&types::SockAddr>) -> Self {
+        Connect::new(fd, addr.as_ptr(), addr.len())
+    }
+}
+
 // === 5.6 ===
 
 opcode! {
@@ -924,7 +1144,14 @@ opcode! {
         offset: u64 = 0,
         ioprio: u16 = 0,
         rw_flags: i32 = 0,
-        buf_group: u16 = 0
+        buf_group: u16 = 0,
+        /// Attach T10 protection information to the request.
+        ///
+        /// The [`PiAttr`](types::PiAttr) itself is read when the request is submitted, but the
+        /// integrity metadata buffer it points to is used by the kernel until the request
+        /// completes. Like the data buffer, it must be valid for the entire duration of the
+        /// operation, otherwise it may cause memory problems.
+        attr_pi: *const types::PiAttr = ptr::null()
     }
 
     pub const CODE = sys::IORING_OP_READ;
@@ -934,7 +1161,7 @@ opcode! {
             fd,
             buf, len, offset,
             ioprio, rw_flags,
-            buf_group
+            buf_group, attr_pi
         } = self;
 
         let mut sqe = sqe_zeroed();
@@ -946,6 +1173,7 @@ opcode! {
         sqe.__bindgen_anon_1.off = offset;
         sqe.__bindgen_anon_3.rw_flags = rw_flags as _;
         sqe.__bindgen_anon_4.buf_group = buf_group;
+        set_attr_pi(&mut sqe, attr_pi);
         Entry(sqe)
     }
 }
@@ -973,7 +1201,14 @@ opcode! {
         /// like the `read(2)` and `write(2)` system calls.
         offset: u64 = 0,
         ioprio: u16 = 0,
-        rw_flags: i32 = 0
+        rw_flags: i32 = 0,
+        /// Attach T10 protection information to the request.
+        ///
+        /// The [`PiAttr`](types::PiAttr) itself is read when the request is submitted, but the
+        /// integrity metadata buffer it points to is used by the kernel until the request
+        /// completes. Like the data buffer, it must be valid for the entire duration of the
+        /// operation, otherwise it may cause memory problems.
+        attr_pi: *const types::PiAttr = ptr::null()
     }
 
     pub const CODE = sys::IORING_OP_WRITE;
@@ -982,7 +1217,7 @@ opcode! {
         let Write {
             fd,
             buf, len, offset,
-            ioprio, rw_flags
+            ioprio, rw_flags, attr_pi
         } = self;
 
         let mut sqe = sqe_zeroed();
@@ -993,6 +1228,7 @@ opcode! {
         sqe.len = len;
         sqe.__bindgen_anon_1.off = offset;
         sqe.__bindgen_anon_3.rw_flags = rw_flags as _;
+        set_attr_pi(&mut sqe, attr_pi);
         Entry(sqe)
     }
 }
@@ -1652,7 +1888,8 @@ opcode! {
         sqe.opcode = Self::CODE;
         sqe.fd = builder.to_fd();
         sqe.__bindgen_anon_2.addr = builder.user_data.unwrap_or(0);
-        sqe.__bindgen_anon_3.cancel_flags = builder.flags.bits();
+        sqe.__bindgen_anon_3.cancel_flags = builder.to_flags();
+        sqe.len = builder.opcode.unwrap_or(0).into();
         Entry(sqe)
     }
 }
@@ -1854,7 +2091,8 @@ opcode! {
     ///
     /// This operation might result in two completion queue entries.
     /// See the `IORING_OP_SEND_ZC` section at [io_uring_enter][] for the exact semantics.
-    /// Notifications posted by this operation can be checked with [notif](crate::cqueue::notif).
+    /// Notifications posted by this operation can be checked with [notif](crate::cqueue::notif),
+    /// or paired with the results by a [`SendZcTracker`](crate::send_zc::SendZcTracker).
     ///
     /// [io_uring_enter]: https://man7.org/linux/man-pages/man2/io_uring_enter.2.html
     pub struct SendZc {
@@ -1873,12 +2111,18 @@ opcode! {
         dest_addr_len: libc::socklen_t = 0,
         flags: i32 = 0,
         zc_flags: u16 = 0,
+        /// Report in the notification whether the data was copied after all, instead of being
+        /// sent from the buffer. See
+        /// [`SendZcCompletion::copied`](crate::send_zc::SendZcCompletion::copied).
+        report_usage: bool = false,
     }
 
     pub const CODE = sys::IORING_OP_SEND_ZC;
 
     pub fn build(self) -> Entry {
-        let SendZc { fd, buf, len, buf_index, dest_addr, dest_addr_len, flags, zc_flags } = self;
+        let SendZc {
+            fd, buf, len, buf_index, dest_addr, dest_addr_len, flags, zc_flags, report_usage
+        } = self;
 
         let mut sqe = sqe_zeroed();
         sqe.opcode = Self::CODE;
@@ -1887,6 +2131,9 @@ opcode! {
         sqe.len = len;
         sqe.__bindgen_anon_3.msg_flags = flags as _;
         sqe.ioprio = zc_flags;
+        if report_usage {
+            sqe.ioprio |= sys::IORING_SEND_ZC_REPORT_USAGE as u16;
+        }
         if let Some(buf_index) = buf_index {
             sqe.__bindgen_anon_4.buf_index = buf_index;
             sqe.ioprio |= sys::IORING_RECVSEND_FIXED_BUF as u16;
@@ -1910,18 +2157,25 @@ opcode! {
         msg: { *const libc::msghdr },
         ;;
         ioprio: u16 = 0,
-        flags: u32 = 0
+        flags: u32 = 0,
+        /// Report in the notification whether the data was copied after all, instead of being
+        /// sent from the buffers. See
+        /// [`SendZcCompletion::copied`](crate::send_zc::SendZcCompletion::copied).
+        report_usage: bool = false
     }
 
     pub const CODE = sys::IORING_OP_SENDMSG_ZC;
 
     pub fn build(self) -> Entry {
-        let SendMsgZc { fd, msg, ioprio, flags } = self;
+        let SendMsgZc { fd, msg, ioprio, flags, report_usage } = self;
 
         let mut sqe = sqe_zeroed();
         sqe.opcode = Self::CODE;
         assign_fd!(sqe.fd = fd);
         sqe.ioprio = ioprio;
+        if report_usage {
+            sqe.ioprio |= sys::IORING_SEND_ZC_REPORT_USAGE as u16;
+        }
         sqe.__bindgen_anon_2.addr = msg as _;
         sqe.len = 1;
         sqe.__bindgen_anon_3.msg_flags = flags;
@@ -2263,6 +2517,16 @@ opcode! {
     }
 }
 
+impl Bind {
+    /// Bind a socket to `addr`.
+    ///
+    /// `addr` must not be dropped until the request has completed.
+    #[inline]
+    pub fn with_addr(fd: impl sealed::UseFixed, addr: Pin<&types::SockAddr>) -> Self {
+        Bind::new(fd, addr.as_ptr(), addr.len())
+    }
+}
+
 opcode! {
     /// Listen on a socket, equivalent to `listen(2)`.
     pub struct Listen {
@@ -2425,3 +2689,58 @@ opcode! {
         Entry(sqe)
     }
 }
+
+// === Result decoding ===
+
+macro_rules! decode {
+    ( $decode:ident -> $output:ty : $( $name:ident ),* $(,)? ) => {
+        $(
+            impl cqueue::Decode for $name {
+                type Output = $output;
+
+                #[inline]
+                fn decode(cqe: &cqueue::Entry) -> Self::Output {
+                    cqueue::$decode(cqe)
+                }
+            }
+        )*
+    };
+}
+
+decode! {
+    decode_len -> io::Result<usize>:
+    Readv, Writev, ReadFixed, WriteFixed, ReadvFixed, WritevFixed, Read, Write,
+    RecvMsg, SendMsg, Recv, Send, SendZc, SendMsgZc, SendBundle, Splice, Tee, GetXattr, FGetXattr,
+    GetSockOpt, SiocInq, SiocOutQ, FilesUpdate, FutexWake, FutexWaitV, EpollWait, AsyncCancel2,
+}
+
+decode! {
+    decode_mask -> io::Result<u32>:
+    PollAdd,
+}
+
+decode! {
+    decode_selected -> io::Result<cqueue::SelectedBuffer>:
+    RecvMulti, RecvMsgMulti, ReadMulti, RecvBundle, RecvMultiBundle,
+}
+
+impl cqueue::DecodeFd for Accept {}
+impl cqueue::DecodeFd for AcceptMulti {}
+impl cqueue::DecodeFd for OpenAt {}
+impl cqueue::DecodeFd for OpenAt2 {}
+impl cqueue::DecodeFd for Socket {}
+impl cqueue::DecodeFd for FixedFdInstall {}
+
+decode! {
+    decode_timeout -> io::Result<()>:
+    Timeout,
+}
+
+decode! {
+    decode_unit -> io::Result<()>:
+    Nop, Fsync, SyncFileRange, Fallocate, Ftruncate, Fadvise, Madvise, Statx, Close,
+    PollRemove, PollUpdate, TimeoutRemove, TimeoutUpdate, AsyncCancel,
+    Connect, Bind, Listen, Shutdown, SetSockOpt, EpollCtl,
+    RenameAt, UnlinkAt, MkDirAt, SymlinkAt, LinkAt, SetXattr, FSetXattr,
+    MsgRingData, MsgRingSendFd, FutexWait, WaitId, Pipe,
+}
@@ -26,6 +26,33 @@ pub(crate) fn execute(
     unsafe { sys::io_uring_register(fd, opcode, arg, len) }
 }
 
+/// Post a completion event to the ring `ring_fd`, as [`MsgRingData`](crate::opcode::MsgRingData)
+/// does, but synchronously and without needing a ring to submit it from. This can be used from
+/// any thread, for example to wake up a thread waiting on the target ring.
+///
+/// The completion event carries `result` and `user_data`, and `user_flags` as its flags if set.
+///
+/// Available since 6.13.
+pub fn send_msg_ring(
+    ring_fd: RawFd,
+    result: i32,
+    user_data: u64,
+    user_flags: Option<u32>,
+) -> io::Result<()> {
+    let entry =
+        crate::opcode::MsgRingData::new(crate::types::Fd(ring_fd), result, user_data, user_flags)
+            .build();
+
+    // The message is not sent from any ring, so the kernel expects no ring descriptor.
+    execute(
+        RegisterRing::RawFd(-1),
+        sys::IORING_REGISTER_SEND_MSG_RING,
+        crate::util::cast_ptr::<sys::io_uring_sqe>(&entry.0).cast(),
+        1,
+    )
+    .map(drop)
+}
+
 /// Information about what `io_uring` features the kernel supports.
 ///
 /// You can fill this in with [`register_probe`](crate::Submitter::register_probe).
@@ -0,0 +1,386 @@
+//! Registered buffer pools.
+//!
+//! [`RegisteredBuffers`] allocates a set of buffers and registers them with the kernel, so that
+//! fixed-buffer requests such as [`ReadFixed`](opcode::ReadFixed) can use them without the kernel
+//! mapping the memory on every request. The memory is owned by the pool and outlives the
+//! registration, which is what makes this safe where
+//! [`register_buffers`](Submitter::register_buffers) is not. Each buffer is checked out as a
+//! [`FixedBuf`], which builds requests with the right buffer index and bounds.
+
+use std::cell::Cell;
+use std::os::unix::io::AsRawFd;
+use std::rc::Rc;
+use std::{fmt, io, ops, ptr, slice};
+
+use crate::register::{execute, RegisterRing};
+use crate::types::sealed;
+use crate::util::{cast_ptr, Mmap, OwnedFd};
+use crate::{opcode, sys, Submitter};
+
+/// A pool of buffers registered with the kernel.
+///
+/// The buffers are unregistered and their memory released once the `RegisteredBuffers` and all
+/// the [`FixedBuf`]s checked out from it have been dropped. Requests using the buffers must have
+/// completed by then.
+///
+/// The buffers are registered without tags, so the kernel posts no completion when it lets go of
+/// them after they are unregistered or replaced, and the pool does not track when that happens.
+///
+/// Cloning a `RegisteredBuffers` is cheap and refers to the same pool.
+#[derive(Clone)]
+pub struct RegisteredBuffers {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    offset: Option<u32>,
+    buf_cnt: u16,
+    buf_len: usize,
+    // The distance between buffers, which keeps each of them page aligned.
+    stride: usize,
+
+    mmap: Mmap,
+    checked_out: Box<[Cell<bool>]>,
+
+    // A duplicate of the ring file descriptor, so that the buffers can be unregistered before
+    // their memory is released even if the `IoUring` has already been dropped.
+    fd: OwnedFd,
+}
+
+/// Builder for [`RegisteredBuffers`].
+#[derive(Debug, Clone, Copy)]
+pub struct Builder {
+    buf_cnt: u16,
+    buf_len: usize,
+    offset: Option<u32>,
+}
+
+impl Builder {
+    /// Set the number of buffers, which must be between 1 and 16384. Defaults to 16.
+    pub fn buf_cnt(&mut self, buf_cnt: u16) -> &mut Self {
+        self.buf_cnt = buf_cnt;
+        self
+    }
+
+    /// Set the length of each buffer, which must not exceed 1 GiB. Defaults to 4096.
+    ///
+    /// Every buffer starts on a page boundary, whatever its length.
+    pub fn buf_len(&mut self, buf_len: usize) -> &mut Self {
+        self.buf_len = buf_len;
+        self
+    }
+
+    /// Install the buffers into the slots starting at `offset` of a buffer table registered
+    /// with [`register_buffers_sparse`](Submitter::register_buffers_sparse), instead of
+    /// registering a table of their own. The slots are emptied again on drop.
+    ///
+    /// This lets several pools, or a pool and other buffers, share the table of one ring.
+    pub fn offset(&mut self, offset: u32) -> &mut Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Allocate the buffers and register them with the ring of `submitter`.
+    ///
+    /// Fails with `InvalidInput` if the sizes are invalid.
+    pub fn build(&self, submitter: &Submitter<'_>) -> io::Result<RegisteredBuffers> {
+        if self.buf_cnt == 0
+            || self.buf_cnt > 1 << 14
+            || self.buf_len == 0
+            || self.buf_len > 1 << 30
+            // Buffer indexes are 16 bits wide in requests.
+            || self.offset.unwrap_or(0) as u64 + self.buf_cnt as u64 > 1 << 16
+        {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let stride = (self.buf_len + page_size - 1) & !(page_size - 1);
+        let mmap = Mmap::new_anon(self.buf_cnt as usize * stride)?;
+
+        let fd = submitter.dup_fd()?;
+
+        let iovecs: Vec<libc::iovec> = (0..self.buf_cnt as usize)
+            .map(|i| libc::iovec {
+                iov_base: unsafe { mmap.as_mut_ptr().cast::<u8>().add(i * stride).cast() },
+                iov_len: self.buf_len,
+            })
+            .collect();
+
+        // Safety: the memory stays valid until the buffers are unregistered when `Inner` is
+        // dropped.
+        unsafe {
+            match self.offset {
+                Some(offset) => submitter.register_buffers_update(offset, &iovecs, None)?,
+                None => submitter.register_buffers(&iovecs)?,
+            }
+        }
+
+        let inner = Inner {
+            offset: self.offset,
+            buf_cnt: self.buf_cnt,
+            buf_len: self.buf_len,
+            stride,
+            mmap,
+            checked_out: (0..self.buf_cnt).map(|_| Cell::new(false)).collect(),
+            fd,
+        };
+
+        Ok(RegisteredBuffers {
+            inner: Rc::new(inner),
+        })
+    }
+}
+
+impl RegisteredBuffers {
+    /// Create a [`Builder`] for a pool.
+    pub fn builder() -> Builder {
+        Builder {
+            buf_cnt: 16,
+            buf_len: 4096,
+            offset: None,
+        }
+    }
+
+    /// The number of buffers.
+    pub fn buf_cnt(&self) -> u16 {
+        self.inner.buf_cnt
+    }
+
+    /// The length of each buffer.
+    pub fn buf_len(&self) -> usize {
+        self.inner.buf_len
+    }
+
+    /// Check out buffer `i` of the pool, which is installed at buffer index
+    /// [`FixedBuf::buf_index`] of the ring.
+    ///
+    /// Returns `None` if it is already checked out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn get(&self, i: u16) -> Option<FixedBuf> {
+        assert!(i < self.inner.buf_cnt, "buffer out of bounds");
+
+        let checked_out = &self.inner.checked_out[i as usize];
+        if checked_out.replace(true) {
+            return None;
+        }
+
+        Some(FixedBuf {
+            pool: self.inner.clone(),
+            i,
+        })
+    }
+
+    /// Check out any buffer of the pool.
+    ///
+    /// Returns `None` if all of them are checked out.
+    pub fn checkout(&self) -> Option<FixedBuf> {
+        (0..self.inner.buf_cnt).find_map(|i| self.get(i))
+    }
+}
+
+impl fmt::Debug for RegisteredBuffers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegisteredBuffers")
+            .field("offset", &self.inner.offset)
+            .field("buf_cnt", &self.inner.buf_cnt)
+            .field("buf_len", &self.inner.buf_len)
+            .finish()
+    }
+}
+
+impl Inner {
+    fn buf_ptr(&self, i: u16) -> *mut u8 {
+        unsafe {
+            self.mmap
+                .as_mut_ptr()
+                .cast::<u8>()
+                .add(i as usize * self.stride)
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let fd = RegisterRing::RawFd(self.fd.as_raw_fd());
+
+        // Nothing can be done about a failure here, the buffers are gone along with the ring
+        // anyway.
+        let _ = match self.offset {
+            Some(offset) => {
+                let iovecs = vec![
+                    libc::iovec {
+                        iov_base: ptr::null_mut(),
+                        iov_len: 0,
+                    };
+                    self.buf_cnt as usize
+                ];
+                let rr = sys::io_uring_rsrc_update2 {
+                    offset,
+                    nr: iovecs.len() as _,
+                    data: iovecs.as_ptr() as _,
+                    ..Default::default()
+                };
+                execute(
+                    fd,
+                    sys::IORING_REGISTER_BUFFERS_UPDATE,
+                    cast_ptr::<sys::io_uring_rsrc_update2>(&rr).cast(),
+                    std::mem::size_of::<sys::io_uring_rsrc_update2>() as _,
+                )
+            }
+            None => execute(fd, sys::IORING_UNREGISTER_BUFFERS, ptr::null(), 0),
+        };
+    }
+}
+
+/// A buffer checked out from [`RegisteredBuffers`], which is handed back to the pool when
+/// dropped.
+///
+/// The requests built from it refer to its memory by pointer: the `FixedBuf` must be kept alive,
+/// and not be accessed in a way that conflicts with the request, until they have completed.
+pub struct FixedBuf {
+    pool: Rc<Inner>,
+    i: u16,
+}
+
+impl FixedBuf {
+    /// The index of the buffer in the ring's buffer table.
+    pub fn buf_index(&self) -> u16 {
+        // Checked not to overflow by the builder.
+        self.pool.offset.unwrap_or(0) as u16 + self.i
+    }
+
+    /// A pointer to the start of the buffer.
+    pub fn as_mut_ptr(&self) -> *mut u8 {
+        self.pool.buf_ptr(self.i)
+    }
+
+    /// An `iovec` for `range` of the buffer, for use with
+    /// [`readv_fixed`](Self::readv_fixed) and [`writev_fixed`](Self::writev_fixed).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn iovec(&self, range: ops::Range<usize>) -> libc::iovec {
+        self.check(&range);
+        libc::iovec {
+            iov_base: unsafe { self.as_mut_ptr().add(range.start).cast() },
+            iov_len: range.len(),
+        }
+    }
+
+    /// Build a [`ReadFixed`](opcode::ReadFixed) reading into `range` of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn read_fixed(
+        &mut self,
+        fd: impl sealed::UseFixed,
+        range: ops::Range<usize>,
+    ) -> opcode::ReadFixed {
+        self.check(&range);
+        opcode::ReadFixed::new(
+            fd,
+            unsafe { self.as_mut_ptr().add(range.start) },
+            range.len() as _,
+            self.buf_index(),
+        )
+    }
+
+    /// Build a [`WriteFixed`](opcode::WriteFixed) writing `range` of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn write_fixed(
+        &self,
+        fd: impl sealed::UseFixed,
+        range: ops::Range<usize>,
+    ) -> opcode::WriteFixed {
+        self.check(&range);
+        opcode::WriteFixed::new(
+            fd,
+            unsafe { self.as_mut_ptr().add(range.start) },
+            range.len() as _,
+            self.buf_index(),
+        )
+    }
+
+    /// Build a [`ReadvFixed`](opcode::ReadvFixed) reading into `iovecs`, which must stay alive
+    /// until the request has been submitted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an `iovec` is not within the buffer, see [`iovec`](Self::iovec).
+    pub fn readv_fixed(
+        &mut self,
+        fd: impl sealed::UseFixed,
+        iovecs: &[libc::iovec],
+    ) -> opcode::ReadvFixed {
+        self.check_iovecs(iovecs);
+        opcode::ReadvFixed::new(fd, iovecs.as_ptr(), iovecs.len() as _, self.buf_index())
+    }
+
+    /// Build a [`WritevFixed`](opcode::WritevFixed) writing `iovecs`, which must stay alive
+    /// until the request has been submitted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an `iovec` is not within the buffer, see [`iovec`](Self::iovec).
+    pub fn writev_fixed(
+        &self,
+        fd: impl sealed::UseFixed,
+        iovecs: &[libc::iovec],
+    ) -> opcode::WritevFixed {
+        self.check_iovecs(iovecs);
+        opcode::WritevFixed::new(fd, iovecs.as_ptr(), iovecs.len() as _, self.buf_index())
+    }
+
+    fn check(&self, range: &ops::Range<usize>) {
+        assert!(
+            range.start <= range.end && range.end <= self.pool.buf_len,
+            "range out of bounds"
+        );
+    }
+
+    fn check_iovecs(&self, iovecs: &[libc::iovec]) {
+        let start = self.as_mut_ptr() as usize;
+        for iovec in iovecs {
+            let offset = (iovec.iov_base as usize).wrapping_sub(start);
+            self.check(&(offset..offset.saturating_add(iovec.iov_len)));
+        }
+    }
+}
+
+impl ops::Deref for FixedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.as_mut_ptr(), self.pool.buf_len) }
+    }
+}
+
+impl ops::DerefMut for FixedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), self.pool.buf_len) }
+    }
+}
+
+impl fmt::Debug for FixedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FixedBuf")
+            .field("buf_index", &self.buf_index())
+            .field("len", &self.pool.buf_len)
+            .finish()
+    }
+}
+
+impl Drop for FixedBuf {
+    fn drop(&mut self) {
+        self.pool.checked_out[self.i as usize].set(false);
+    }
+}
@@ -0,0 +1,218 @@
+//! Ownership of the state of in-flight requests.
+//!
+//! An [`OpRegistry`] hands out the user_data of each request and keeps whatever the request
+//! points to, such as its buffers, until the kernel has posted its last completion, at which
+//! point the state is handed back.
+//!
+//! This module is only available with the `registry` feature, which the `driver` feature
+//! enables.
+
+use std::{fmt, mem};
+
+use crate::{cqueue, squeue, SubmissionQueue};
+
+/// A table of the requests in flight, keyed by their user_data.
+///
+/// Each request is entered along with its state, and gets a user_data value made of the index of
+/// its slot and a generation that changes every time the slot is reused, so that a stale
+/// user_data never refers to a later request. Every completion queue entry is then handed to
+/// [`complete`](Self::complete), which returns the state once the request has completed for
+/// good. Multishot requests stay registered while their completions have
+/// [`more`](cqueue::more) set.
+///
+/// The state is moved into the registry, so the data the request points to must not move along
+/// with it: keep it in a `Vec<u8>`, a `Box` or similar rather than inline in the state.
+///
+/// Dropping the registry leaks the state of the requests still in flight, as the kernel may
+/// still be using it.
+pub struct OpRegistry<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+struct Slot<T> {
+    generation: u32,
+    state: Option<T>,
+}
+
+/// What a completion queue entry meant for a registered request, returned by
+/// [`OpRegistry::complete`].
+#[derive(Debug)]
+pub enum Completion<'a, T> {
+    /// The request stays in flight and will complete again, as multishot requests do.
+    More(&'a T),
+    /// The request is done, and its state is handed back.
+    Done(T),
+}
+
+impl<T> OpRegistry<T> {
+    /// Create an empty registry.
+    pub fn new() -> OpRegistry<T> {
+        OpRegistry::with_capacity(0)
+    }
+
+    /// Create an empty registry with room for `capacity` requests before it allocates.
+    pub fn with_capacity(capacity: usize) -> OpRegistry<T> {
+        OpRegistry {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Enter `state` and return the user_data to submit its request with.
+    ///
+    /// Use [`push`](Self::push) to enter the state and submit the request at once. Otherwise,
+    /// the request must be submitted with the returned user_data, or the state removed again
+    /// with [`remove`](Self::remove).
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `u32::MAX` requests are registered.
+    pub fn insert(&mut self, state: T) -> u64 {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("too many requests");
+                self.slots.push(Slot {
+                    generation: 0,
+                    state: None,
+                });
+                index
+            }
+        };
+
+        let slot = &mut self.slots[index as usize];
+        slot.state = Some(state);
+        self.len += 1;
+
+        (u64::from(slot.generation) << 32) | u64::from(index)
+    }
+
+    /// Enter `state` and push `entry` to `sq` with the user_data of the state, which is
+    /// returned.
+    ///
+    /// If the queue is full, the state is handed back as the error.
+    ///
+    /// # Safety
+    ///
+    /// Developers must ensure that parameters of the entry (such as buffer) are valid for as
+    /// long as `state` is kept by the registry, for example by pointing into memory owned by
+    /// `state`.
+    pub unsafe fn push<E: squeue::EntryMarker>(
+        &mut self,
+        sq: &mut SubmissionQueue<'_, E>,
+        mut entry: E,
+        state: T,
+    ) -> Result<u64, T> {
+        if sq.is_full() {
+            return Err(state);
+        }
+
+        let user_data = self.insert(state);
+        entry.set_user_data(user_data);
+        sq.push(&entry).expect("queue is full");
+
+        Ok(user_data)
+    }
+
+    /// Remove the state registered under `user_data`, if any.
+    ///
+    /// # Safety
+    ///
+    /// The request of the state must not be in flight: it must either never have been
+    /// submitted, or have completed already.
+    pub unsafe fn remove(&mut self, user_data: u64) -> Option<T> {
+        let index = self.index(user_data)?;
+        let slot = &mut self.slots[index];
+        let state = slot.state.take()?;
+
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index as u32);
+        self.len -= 1;
+
+        Some(state)
+    }
+
+    /// Account for a completion queue entry.
+    ///
+    /// Returns `None` if the entry does not belong to a registered request, so that it can be
+    /// handled elsewhere.
+    pub fn complete(&mut self, cqe: &cqueue::Entry) -> Option<Completion<'_, T>> {
+        let user_data = cqe.user_data();
+        let index = self.index(user_data)?;
+
+        if cqueue::more(cqe.flags()) {
+            self.slots[index].state.as_ref().map(Completion::More)
+        } else {
+            // Safety: this was the last completion of the request.
+            unsafe { self.remove(user_data) }.map(Completion::Done)
+        }
+    }
+
+    /// The state registered under `user_data`, if any.
+    pub fn get(&self, user_data: u64) -> Option<&T> {
+        let index = self.index(user_data)?;
+        self.slots[index].state.as_ref()
+    }
+
+    /// The state registered under `user_data`, if any.
+    ///
+    /// # Safety
+    ///
+    /// If the request of the state is in flight, the state must not be changed in a way that
+    /// moves or frees the data the request points to, such as by replacing or resizing its
+    /// buffers, for as long as the kernel may still use it.
+    pub unsafe fn get_mut(&mut self, user_data: u64) -> Option<&mut T> {
+        let index = self.index(user_data)?;
+        self.slots[index].state.as_mut()
+    }
+
+    /// Whether a request is registered under `user_data`.
+    pub fn contains(&self, user_data: u64) -> bool {
+        self.get(user_data).is_some()
+    }
+
+    /// The number of registered requests.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no requests are registered.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn index(&self, user_data: u64) -> Option<usize> {
+        let index = user_data as u32 as usize;
+        let generation = (user_data >> 32) as u32;
+
+        match self.slots.get(index) {
+            Some(slot) if slot.generation == generation => Some(index),
+            _ => None,
+        }
+    }
+}
+
+impl<T> Default for OpRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for OpRegistry<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpRegistry")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<T> Drop for OpRegistry<T> {
+    fn drop(&mut self) {
+        for slot in self.slots.drain(..) {
+            mem::forget(slot.state);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Tracking of zero-copy sends.
+//!
+//! A [`SendZc`](crate::opcode::SendZc) or [`SendMsgZc`](crate::opcode::SendMsgZc) request
+//! usually completes twice: once with the result of the send, and once more with a notification
+//! that the kernel no longer uses the buffer. A [`SendZcTracker`] pairs the two by user_data and
+//! holds on to the buffer until it may be reused.
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::{fmt, mem};
+
+use crate::{cqueue, sys};
+
+/// Keeps the buffers of zero-copy sends until the kernel is done with them.
+///
+/// Each send is tracked by the user_data of its request with [`track`](Self::track), and every
+/// completion queue entry is handed to [`complete`](Self::complete), which releases the buffer
+/// once both the result and the notification of the send have arrived.
+///
+/// The buffer is moved into the tracker, so its data must not move along with it: use something
+/// like a `Vec<u8>` or `Box<[u8]>` rather than an array. Anything else that the request points to,
+/// such as the `msghdr` of a [`SendMsgZc`](crate::opcode::SendMsgZc), can be made part of the
+/// buffer as well.
+///
+/// Dropping the tracker leaks the buffers of the sends still in flight, as the kernel may still
+/// be using them.
+pub struct SendZcTracker<B> {
+    pending: HashMap<u64, Pending<B>>,
+}
+
+struct Pending<B> {
+    buf: B,
+    result: Option<i32>,
+    notif: Option<i32>,
+}
+
+/// What a completion queue entry meant for a tracked send, returned by
+/// [`SendZcTracker::complete`].
+#[derive(Debug)]
+pub enum SendZcEvent<B> {
+    /// The send completed with this result, but the kernel still uses the buffer until the
+    /// notification arrives.
+    Sent(i32),
+    /// The notification arrived before the result of the send, which releases the buffer once it
+    /// arrives as well. The kernel posts them the other way around, so this is not expected.
+    Notified,
+    /// The kernel is done with the buffer, which is handed back.
+    Released(SendZcCompletion<B>),
+}
+
+/// A finished zero-copy send, along with its buffer.
+#[derive(Debug)]
+pub struct SendZcCompletion<B> {
+    buf: B,
+    result: i32,
+    copied: bool,
+}
+
+impl<B> SendZcTracker<B> {
+    /// Create a tracker with no sends in flight.
+    pub fn new() -> SendZcTracker<B> {
+        SendZcTracker {
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Keep `buf` until the send with the given user_data has completed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a send with the same user_data is already being tracked.
+    pub fn track(&mut self, user_data: u64, buf: B) -> &B {
+        match self.pending.entry(user_data) {
+            Entry::Occupied(_) => {
+                panic!("user_data {} is already tracked", user_data)
+            }
+            Entry::Vacant(entry) => {
+                &entry
+                    .insert(Pending {
+                        buf,
+                        result: None,
+                        notif: None,
+                    })
+                    .buf
+            }
+        }
+    }
+
+    /// Whether a send with the given user_data is being tracked.
+    pub fn contains(&self, user_data: u64) -> bool {
+        self.pending.contains_key(&user_data)
+    }
+
+    /// The number of sends being tracked.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether no sends are being tracked.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Account for a completion queue entry.
+    ///
+    /// Returns `None` only if the entry does not belong to a tracked send, so that it can be
+    /// handled elsewhere. Every entry of a tracked send gives an event, including a notification
+    /// arriving before the result, see [`SendZcEvent::Notified`].
+    pub fn complete(&mut self, cqe: &cqueue::Entry) -> Option<SendZcEvent<B>> {
+        let user_data = cqe.user_data();
+        let pending = self.pending.get_mut(&user_data)?;
+
+        if cqueue::notif(cqe.flags()) {
+            pending.notif = Some(cqe.result());
+        } else {
+            pending.result = Some(cqe.result());
+
+            // No notification follows if the send failed before using the buffer.
+            if !cqueue::more(cqe.flags()) {
+                pending.notif = Some(0);
+            }
+        }
+
+        match *pending {
+            Pending {
+                result: Some(result),
+                notif: Some(notif),
+                ..
+            } => {
+                let pending = self.pending.remove(&user_data).unwrap();
+                Some(SendZcEvent::Released(SendZcCompletion {
+                    buf: pending.buf,
+                    result,
+                    copied: notif as u32 & sys::IORING_NOTIF_USAGE_ZC_COPIED != 0,
+                }))
+            }
+            Pending {
+                result: Some(result),
+                notif: None,
+                ..
+            } => Some(SendZcEvent::Sent(result)),
+            // The notification arrived first, wait for the result.
+            Pending { result: None, .. } => Some(SendZcEvent::Notified),
+        }
+    }
+}
+
+impl<B> Default for SendZcTracker<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B> fmt::Debug for SendZcTracker<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendZcTracker")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<B> Drop for SendZcTracker<B> {
+    fn drop(&mut self) {
+        for (_, pending) in self.pending.drain() {
+            mem::forget(pending.buf);
+        }
+    }
+}
+
+impl<B> SendZcCompletion<B> {
+    /// The result of the send, the number of bytes sent or a negated error code.
+    pub fn result(&self) -> i32 {
+        self.result
+    }
+
+    /// Whether the kernel copied the data after all instead of sending it from the buffer, for
+    /// example because the socket does not support zero-copy.
+    ///
+    /// This is only reported for sends built with `report_usage`, and is `false` otherwise.
+    pub fn copied(&self) -> bool {
+        self.copied
+    }
+
+    /// The buffer of the send.
+    pub fn buf(&self) -> &B {
+        &self.buf
+    }
+
+    /// Take back the buffer of the send.
+    pub fn into_buf(self) -> B {
+        self.buf
+    }
+}
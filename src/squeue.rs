@@ -6,7 +6,7 @@ use std::mem;
 use std::sync::atomic;
 
 use crate::sys;
-use crate::util::{private, unsync_load, Mmap};
+use crate::util::{private, unsync_load, Region};
 
 use bitflags::bitflags;
 
@@ -174,22 +174,22 @@ bitflags! {
 impl<E: EntryMarker> Inner<E> {
     #[rustfmt::skip]
     pub(crate) unsafe fn new(
-        sq_mmap: &Mmap,
-        sqe_mmap: &Mmap,
+        sq_ring: &Region,
+        sqes: &Region,
         p: &sys::io_uring_params,
     ) -> Self {
-        let head         = sq_mmap.offset(p.sq_off.head        ) as *const atomic::AtomicU32;
-        let tail         = sq_mmap.offset(p.sq_off.tail        ) as *const atomic::AtomicU32;
-        let ring_mask    = sq_mmap.offset(p.sq_off.ring_mask   ).cast::<u32>().read();
-        let ring_entries = sq_mmap.offset(p.sq_off.ring_entries).cast::<u32>().read();
-        let flags        = sq_mmap.offset(p.sq_off.flags       ) as *const atomic::AtomicU32;
-        let dropped      = sq_mmap.offset(p.sq_off.dropped     ) as *const atomic::AtomicU32;
-        let sqes         = sqe_mmap.as_mut_ptr() as *mut E;
+        let head         = sq_ring.offset(p.sq_off.head        ) as *const atomic::AtomicU32;
+        let tail         = sq_ring.offset(p.sq_off.tail        ) as *const atomic::AtomicU32;
+        let ring_mask    = sq_ring.offset(p.sq_off.ring_mask   ).cast::<u32>().read();
+        let ring_entries = sq_ring.offset(p.sq_off.ring_entries).cast::<u32>().read();
+        let flags        = sq_ring.offset(p.sq_off.flags       ) as *const atomic::AtomicU32;
+        let dropped      = sq_ring.offset(p.sq_off.dropped     ) as *const atomic::AtomicU32;
+        let sqes         = sqes.as_mut_ptr() as *mut E;
 
         // Initialize the SQ array with an identity mapping unless NO_SQARRAY is set, in which case
         // the kernel consumes SQEs directly by ring index and no array exists.
         if p.flags & sys::IORING_SETUP_NO_SQARRAY == 0 {
-            let array = sq_mmap.offset(p.sq_off.array) as *mut u32;
+            let array = sq_ring.offset(p.sq_off.array) as *mut u32;
             for i in 0..ring_entries {
                 array.add(i as usize).write_volatile(i);
             }
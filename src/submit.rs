@@ -1,4 +1,4 @@
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
 use std::sync::atomic;
 use std::{io, mem, ptr};
 
@@ -6,7 +6,7 @@ use crate::register::{execute, Probe, RegisterRing};
 use crate::sys;
 use crate::types::{CancelBuilder, CloneBuffersFlags, Napi, Timespec};
 use crate::util::{cast_ptr, OwnedFd};
-use crate::Parameters;
+use crate::{Parameters, RingFd};
 use bitflags::bitflags;
 
 use crate::register::Restriction;
@@ -50,7 +50,7 @@ bitflags!(
 /// io_uring supports both directly performing I/O on buffers and file descriptors and registering
 /// them beforehand. Registering is slow, but it makes performing the actual I/O much faster.
 pub struct Submitter<'a> {
-    fd: &'a OwnedFd,
+    fd: &'a RingFd,
     params: &'a Parameters,
     enter_ring_fd: i32,
 
@@ -62,22 +62,46 @@ pub struct Submitter<'a> {
 impl<'a> Submitter<'a> {
     #[inline]
     pub(crate) const fn new(
-        fd: &'a OwnedFd,
+        fd: &'a RingFd,
         params: &'a Parameters,
         sq_head: *const atomic::AtomicU32,
         sq_tail: *const atomic::AtomicU32,
         sq_flags: *const atomic::AtomicU32,
     ) -> Submitter<'a> {
+        // A ring without a file descriptor is always entered through its registered index.
+        let enter_ring_fd = match *fd {
+            RingFd::Fd(_) => -1,
+            RingFd::Registered(index) => index as i32,
+        };
+
         Submitter {
             fd,
             params,
-            enter_ring_fd: -1,
+            enter_ring_fd,
             sq_head,
             sq_tail,
             sq_flags,
         }
     }
 
+    /// Duplicate the ring file descriptor, for types that unregister resources once dropped,
+    /// which may be after the ring itself.
+    ///
+    /// Fails with `EBADF` if the ring only has a registered file descriptor.
+    pub(crate) fn dup_fd(&self) -> io::Result<OwnedFd> {
+        let fd = self
+            .fd
+            .as_owned_fd()
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::EBADF))?;
+
+        unsafe {
+            match libc::fcntl(fd.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0) {
+                -1 => Err(io::Error::last_os_error()),
+                fd => Ok(OwnedFd::from_raw_fd(fd)),
+            }
+        }
+    }
+
     #[inline]
     fn sq_len(&self) -> usize {
         unsafe {
@@ -117,10 +141,11 @@ impl<'a> Submitter<'a> {
     #[inline]
     fn register_ring(&self) -> RegisterRing {
         let enter_ring_fd = self.enter_ring_fd;
-        if enter_ring_fd >= 0 && self.params.is_feature_reg_reg_ring() {
-            RegisterRing::RegisteredIndex(enter_ring_fd)
-        } else {
-            RegisterRing::RawFd(self.fd.as_raw_fd())
+        match self.fd {
+            RingFd::Fd(fd) if enter_ring_fd < 0 || !self.params.is_feature_reg_reg_ring() => {
+                RegisterRing::RawFd(fd.as_raw_fd())
+            }
+            _ => RegisterRing::RegisteredIndex(enter_ring_fd),
         }
     }
 
@@ -147,15 +172,25 @@ impl<'a> Submitter<'a> {
             .unwrap_or_else(ptr::null);
         let size = mem::size_of::<T>();
 
+        self.enter_raw(to_submit, min_complete, flag, arg, size)
+    }
+
+    unsafe fn enter_raw(
+        &self,
+        to_submit: u32,
+        min_complete: u32,
+        flag: u32,
+        arg: *const libc::c_void,
+        size: usize,
+    ) -> io::Result<usize> {
         // If a ring fd has been registered with [`register_ring_fd`](Self::register_ring_fd),
         // `enter_ring_fd` holds its index (otherwise `-1`); pass it together with
         // `IORING_ENTER_REGISTERED_RING` instead of the raw file descriptor to avoid the per-call
         // fd lookup in the kernel.
         let enter_ring_fd = self.enter_ring_fd;
-        let (fd, flag) = if enter_ring_fd >= 0 {
-            (enter_ring_fd, flag | sys::IORING_ENTER_REGISTERED_RING)
-        } else {
-            (self.fd.as_raw_fd(), flag)
+        let (fd, flag) = match self.fd {
+            RingFd::Fd(fd) if enter_ring_fd < 0 => (fd.as_raw_fd(), flag),
+            _ => (enter_ring_fd, flag | sys::IORING_ENTER_REGISTERED_RING),
         };
 
         sys::io_uring_enter(fd, to_submit, min_complete, flag, arg, size).map(|res| res as _)
@@ -227,6 +262,43 @@ impl<'a> Submitter<'a> {
         let len = self.sq_len();
         let mut flags = EnterFlags::EXT_ARG;
 
+        if args.abs_timer {
+            flags.insert(EnterFlags::ABS_TIMER);
+        }
+
+        let flags = match self.ext_arg_flags(want, flags) {
+            Some(flags) => flags,
+            None => return Ok(len),
+        };
+
+        unsafe { self.enter(len as _, want as _, flags.bits(), Some(&args.args)) }
+    }
+
+    /// Submit all queued submission queue events to the kernel and wait for at least `want`
+    /// completion events to complete, using the wait arguments held in slot `index` of the region
+    /// registered with [`register_mem_region`](Self::register_mem_region).
+    ///
+    /// Unlike [`submit_with_args`](Self::submit_with_args), this does not copy the arguments into
+    /// the kernel on every call. An out of bounds `index` fails with `EFAULT`.
+    ///
+    /// Available since 6.13.
+    pub fn submit_with_registered_args(&self, want: usize, index: u32) -> io::Result<usize> {
+        let len = self.sq_len();
+        let flags = match self.ext_arg_flags(want, EnterFlags::EXT_ARG | EnterFlags::EXT_ARG_REG) {
+            Some(flags) => flags,
+            None => return Ok(len),
+        };
+
+        // The argument is the offset of the slot into the registered region.
+        let size = mem::size_of::<types::RegWait>();
+        let offset = index as usize * size;
+
+        unsafe { self.enter_raw(len as _, want as _, flags.bits(), offset as _, size) }
+    }
+
+    /// Compute the flags to submit with extended arguments, or `None` if the kernel doesn't need
+    /// to be entered.
+    fn ext_arg_flags(&self, want: usize, mut flags: EnterFlags) -> Option<EnterFlags> {
         let sq_cq_overflow = self.sq_cq_overflow();
         let need_syscall = sq_cq_overflow & self.params.is_feature_nodrop();
 
@@ -242,11 +314,11 @@ impl<'a> Submitter<'a> {
             } else if want == 0 && !need_syscall {
                 // The kernel thread is polling and hasn't fallen asleep, so we don't need to tell
                 // it to process events or wake it up
-                return Ok(len);
+                return None;
             }
         }
 
-        unsafe { self.enter(len as _, want as _, flags.bits(), Some(args)) }
+        Some(flags)
     }
 
     /// Wait for the submission queue to have free entries.
@@ -413,7 +485,7 @@ impl<'a> Submitter<'a> {
             ..Default::default()
         };
         execute(
-            RegisterRing::RawFd(self.fd.as_raw_fd()),
+            self.fd.register_ring(),
             sys::IORING_REGISTER_CLONE_BUFFERS,
             cast_ptr::<sys::io_uring_clone_buffers>(&arg).cast(),
             // This opcode takes a single struct; the kernel requires nr_args == 1.
@@ -481,6 +553,89 @@ impl<'a> Submitter<'a> {
         Ok(ret as _)
     }
 
+    /// Variant of [`register_files`](Self::register_files) with resource tagging.
+    ///
+    /// `tags` should be the same length as `fds` and contain the tag value corresponding to the
+    /// file at the same index. Once the kernel has dropped its last reference to a tagged file,
+    /// either because the slot was replaced or because the table was unregistered, a completion
+    /// queue entry is posted with `user_data` set to the tag. Only then is it safe to tear down
+    /// anything the file depends on. See
+    /// [`Entry::resource_tag`](crate::cqueue::Entry::resource_tag) for telling these entries apart
+    /// from request completions.
+    ///
+    /// If a tag is zero, then tagging for this particular resource is disabled. Sparse entries
+    /// (an fd of -1) cannot be tagged.
+    ///
+    /// Available since Linux 5.13.
+    pub fn register_files_tags(&self, fds: &[RawFd], tags: &[u64]) -> io::Result<()> {
+        let rr = sys::io_uring_rsrc_register {
+            nr: fds.len().min(tags.len()) as _,
+            data: fds.as_ptr() as _,
+            tags: tags.as_ptr() as _,
+            ..Default::default()
+        };
+        self.execute_register(
+            sys::IORING_REGISTER_FILES2,
+            cast_ptr::<sys::io_uring_rsrc_register>(&rr).cast(),
+            mem::size_of::<sys::io_uring_rsrc_register>() as _,
+        )
+        .map(drop)
+    }
+
+    /// Variant of [`register_files_update`](Self::register_files_update) with resource tagging.
+    ///
+    /// The file previously installed at each updated slot posts its own tag, if it had one, once
+    /// the kernel has released it. See [`register_files_tags`](Self::register_files_tags) for more
+    /// information about resource tagging.
+    ///
+    /// Returns the number of slots updated.
+    ///
+    /// Available since Linux 5.13.
+    pub fn register_files_update_tag(
+        &self,
+        offset: u32,
+        fds: &[RawFd],
+        tags: &[u64],
+    ) -> io::Result<usize> {
+        let rr = sys::io_uring_rsrc_update2 {
+            offset,
+            nr: fds.len().min(tags.len()) as _,
+            data: fds.as_ptr() as _,
+            tags: tags.as_ptr() as _,
+            ..Default::default()
+        };
+        let ret = self.execute_register(
+            sys::IORING_REGISTER_FILES_UPDATE2,
+            cast_ptr::<sys::io_uring_rsrc_update2>(&rr).cast(),
+            mem::size_of::<sys::io_uring_rsrc_update2>() as _,
+        )?;
+        Ok(ret as _)
+    }
+
+    /// Restrict the slots of the registered file table that the kernel picks from when
+    /// allocating direct descriptors, such as for a
+    /// [`DestinationSlot::auto_target`](crate::types::DestinationSlot::auto_target), to the `len`
+    /// slots starting at `offset`.
+    ///
+    /// The range must lie within the registered file table. Slots outside of it can still be
+    /// targeted explicitly.
+    ///
+    /// Available since 6.0.
+    pub fn register_file_alloc_range(&self, offset: u32, len: u32) -> io::Result<()> {
+        let arg = sys::io_uring_file_index_range {
+            off: offset,
+            len,
+            resv: 0,
+        };
+        self.execute_register(
+            sys::IORING_REGISTER_FILE_ALLOC_RANGE,
+            cast_ptr::<sys::io_uring_file_index_range>(&arg).cast(),
+            // The kernel requires nr_args == 0 for this opcode.
+            0,
+        )
+        .map(drop)
+    }
+
     /// Register an eventfd created by [`eventfd`](libc::eventfd) with the io_uring instance.
     pub fn register_eventfd(&self, eventfd: RawFd) -> io::Result<()> {
         self.execute_register(
@@ -651,10 +806,10 @@ impl<'a> Submitter<'a> {
     ///
     /// Available since Linux 5.18.
     pub fn register_ring_fd(&mut self) -> io::Result<()> {
-        if self.enter_ring_fd >= 0 {
-            return Err(io::Error::from_raw_os_error(libc::EEXIST));
-        }
-        let raw_fd = self.fd.as_raw_fd();
+        let raw_fd = match self.fd {
+            RingFd::Fd(fd) if self.enter_ring_fd < 0 => fd.as_raw_fd(),
+            _ => return Err(io::Error::from_raw_os_error(libc::EEXIST)),
+        };
         let mut up = sys::io_uring_rsrc_update {
             offset: u32::MAX,
             resv: 0,
@@ -672,12 +827,14 @@ impl<'a> Submitter<'a> {
     /// Unregister a ring file descriptor previously registered with
     /// [`register_ring_fd`](Self::register_ring_fd). Subsequent [`enter`](Self::enter) calls revert
     /// to using the raw file descriptor, as do subsequent registration calls. Returns an `EINVAL`
-    /// error if no ring fd is registered.
+    /// error if no ring fd is registered, or if the ring was built with
+    /// [`Builder::setup_registered_fd_only`](crate::Builder::setup_registered_fd_only) and has no
+    /// other file descriptor.
     ///
     /// Available since Linux 5.18.
     pub fn unregister_ring_fd(&mut self) -> io::Result<()> {
         let offset = self.enter_ring_fd;
-        if offset < 0 {
+        if offset < 0 || self.fd.as_owned_fd().is_none() {
             return Err(io::Error::from_raw_os_error(libc::EINVAL));
         }
         let up = sys::io_uring_rsrc_update {
@@ -842,6 +999,25 @@ impl<'a> Submitter<'a> {
         .map(drop)
     }
 
+    /// Get the head of a registered buffer ring, which is the index of the next entry the kernel
+    /// will pick a buffer from.
+    ///
+    /// Comparing it with the tail tells how many buffers are still available to the kernel.
+    ///
+    /// Available since 6.8.
+    pub fn buf_ring_head(&self, bgid: u16) -> io::Result<u16> {
+        let mut arg = sys::io_uring_buf_status {
+            buf_group: bgid as _,
+            ..Default::default()
+        };
+        self.execute_register(
+            sys::IORING_REGISTER_PBUF_STATUS,
+            (&mut arg as *mut sys::io_uring_buf_status).cast(),
+            1,
+        )?;
+        Ok(arg.head as u16)
+    }
+
     /// Performs a synchronous cancellation request, similar to [AsyncCancel](crate::opcode::AsyncCancel),
     /// except that it completes synchronously.
     ///
@@ -877,7 +1053,7 @@ impl<'a> Submitter<'a> {
             tv_nsec: -1,
         });
         let user_data = builder.user_data.unwrap_or(0);
-        let flags = builder.flags.bits();
+        let flags = builder.to_flags();
         let fd = builder.to_fd();
 
         let arg = sys::io_uring_sync_cancel_reg {
@@ -885,6 +1061,7 @@ impl<'a> Submitter<'a> {
             fd,
             flags,
             timeout: timespec,
+            opcode: builder.opcode.unwrap_or(0),
             ..Default::default()
         };
 
@@ -896,6 +1073,27 @@ impl<'a> Submitter<'a> {
         .map(drop)
     }
 
+    /// Set the clock that timeouts passed to [`submit_with_args`](Self::submit_with_args) and
+    /// [`submit_with_registered_args`](Self::submit_with_registered_args) are measured against.
+    ///
+    /// This also applies to absolute deadlines set with
+    /// [`SubmitArgs::abs_timer`](types::SubmitArgs::abs_timer).
+    ///
+    /// Available since 6.12.
+    pub fn register_clock(&self, clock: types::Clock) -> io::Result<()> {
+        let arg = sys::io_uring_clock_register {
+            clockid: clock.clockid() as _,
+            ..Default::default()
+        };
+
+        self.execute_register(
+            sys::IORING_REGISTER_CLOCK,
+            cast_ptr::<sys::io_uring_clock_register>(&arg).cast(),
+            0,
+        )
+        .map(drop)
+    }
+
     /// Register a netdev hw rx queue for zerocopy.
     ///
     /// Available since 6.15.
@@ -907,4 +1105,29 @@ impl<'a> Submitter<'a> {
         )
         .map(drop)
     }
+
+    /// Register a region of memory holding `nr` [`RegWait`](types::RegWait) slots, which can be
+    /// used to wait with [`submit_with_registered_args`](Self::submit_with_registered_args).
+    ///
+    /// Only a single region can be registered per ring, and only while the ring is disabled, see
+    /// [`Builder::setup_r_disabled`](crate::Builder::setup_r_disabled).
+    ///
+    /// Available since 6.13.
+    pub fn register_mem_region(&self, nr: u32) -> io::Result<types::RegWaitRegion> {
+        let region = types::RegWaitRegion::new(nr)?;
+        let mut desc = region.as_desc();
+        let arg = sys::io_uring_mem_region_reg {
+            region_uptr: (&mut desc as *mut sys::io_uring_region_desc) as _,
+            flags: sys::IORING_MEM_REGION_REG_WAIT_ARG as _,
+            ..Default::default()
+        };
+
+        self.execute_register(
+            sys::IORING_REGISTER_MEM_REGION,
+            cast_ptr::<sys::io_uring_mem_region_reg>(&arg).cast(),
+            1,
+        )?;
+
+        Ok(region)
+    }
 }
@@ -38,15 +38,24 @@ pub(crate) mod sealed {
             Target::Fixed(self.0)
         }
     }
+
+    impl UseFixed for Target {
+        #[inline]
+        fn into(self) -> Target {
+            self
+        }
+    }
 }
 
 use crate::sys;
-use crate::util::{cast_ptr, unwrap_nonzero, unwrap_u32};
+use crate::util::{cast_ptr, unwrap_nonzero, unwrap_u32, Mmap};
 use bitflags::bitflags;
 use std::convert::TryFrom;
-use std::marker::PhantomData;
+use std::marker::{PhantomData, PhantomPinned};
 use std::num::NonZeroU32;
 use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::{fmt, io, mem, slice};
 
 #[deprecated]
 pub type RwFlags = u32;
@@ -152,6 +161,17 @@ bitflags! {
         ///
         /// Available since 6.0
         const FD_FIXED = sys::IORING_ASYNC_CANCEL_FD_FIXED;
+
+        /// Match based on the user_data, even when also matching on the file descriptor or
+        /// opcode.
+        ///
+        /// Available since 6.6.
+        const USERDATA = sys::IORING_ASYNC_CANCEL_USERDATA;
+
+        /// Match based on the opcode of the original request.
+        ///
+        /// Available since 6.6.
+        const OP = sys::IORING_ASYNC_CANCEL_OP;
     }
 }
 
@@ -237,6 +257,87 @@ impl From<std::time::Duration> for Timespec {
     }
 }
 
+/// The clock that waits for completions are measured against, set with
+/// [`Submitter::register_clock`](crate::Submitter::register_clock).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    /// `CLOCK_MONOTONIC`, which does not advance while the system is suspended. This is the
+    /// default.
+    Monotonic,
+    /// `CLOCK_BOOTTIME`, which keeps advancing while the system is suspended.
+    Boottime,
+}
+
+impl Clock {
+    pub(crate) const fn clockid(self) -> libc::clockid_t {
+        match self {
+            Clock::Monotonic => libc::CLOCK_MONOTONIC,
+            Clock::Boottime => libc::CLOCK_BOOTTIME,
+        }
+    }
+}
+
+bitflags! {
+    /// Integrity checks for a [`PiAttr`], the `IO_INTEGRITY_CHK_*` flags of `<linux/fs.h>`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    pub struct PiFlags: u16 {
+        /// Check the guard tag.
+        const CHK_GUARD = 1 << 0;
+        /// Check the reference tag.
+        const CHK_REFTAG = 1 << 1;
+        /// Check the application tag.
+        const CHK_APPTAG = 1 << 2;
+    }
+}
+
+/// T10 protection information to attach to a read or write, such as with
+/// [`Read::attr_pi`](crate::opcode::Read::attr_pi).
+///
+/// The integrity metadata lives in a separate buffer, whose layout is defined by the integrity
+/// profile of the device. Only devices with an integrity profile support this, and only with
+/// `O_DIRECT`; see [`Parameters::is_feature_rw_attr`](crate::Parameters::is_feature_rw_attr).
+///
+/// The attribute is copied by the kernel when the request is submitted, so it only needs to stay
+/// valid until then. The metadata buffer is not: the kernel reads or fills it while the request
+/// runs, so it must be valid for the entire duration of the operation, like the data buffer.
+#[derive(Default, Debug, Clone, Copy)]
+#[repr(transparent)]
+pub struct PiAttr(pub(crate) sys::io_uring_attr_pi);
+
+impl PiAttr {
+    /// Attach the `len` bytes of integrity metadata at `addr`, which must stay valid until the
+    /// request has completed.
+    #[inline]
+    pub fn new(addr: *mut u8, len: u32) -> Self {
+        PiAttr(sys::io_uring_attr_pi {
+            addr: addr as _,
+            len,
+            ..Default::default()
+        })
+    }
+
+    /// Set the checks the device performs.
+    #[inline]
+    pub const fn flags(mut self, flags: PiFlags) -> Self {
+        self.0.flags = flags.bits();
+        self
+    }
+
+    /// Set the application tag, checked with [`PiFlags::CHK_APPTAG`].
+    #[inline]
+    pub const fn app_tag(mut self, app_tag: u16) -> Self {
+        self.0.app_tag = app_tag;
+        self
+    }
+
+    /// Set the seed of the reference tag, usually the logical block address of the first block.
+    #[inline]
+    pub const fn seed(mut self, seed: u64) -> Self {
+        self.0.seed = seed;
+        self
+    }
+}
+
 /// Submit arguments
 ///
 /// Note that arguments that exceed their lifetime will fail to compile.
@@ -256,10 +357,10 @@ impl From<std::time::Duration> for Timespec {
 ///
 /// drop(args);
 /// ```
-#[repr(transparent)]
 #[derive(Default, Debug, Clone, Copy)]
 pub struct SubmitArgs<'prev: 'now, 'now> {
     pub(crate) args: sys::io_uring_getevents_arg,
+    pub(crate) abs_timer: bool,
     prev: PhantomData<&'prev ()>,
     now: PhantomData<&'now ()>,
 }
@@ -276,6 +377,7 @@ impl<'prev, 'now> SubmitArgs<'prev, 'now> {
 
         SubmitArgs {
             args,
+            abs_timer: false,
             prev: PhantomData,
             now: PhantomData,
         }
@@ -291,6 +393,7 @@ impl<'prev, 'now> SubmitArgs<'prev, 'now> {
 
         SubmitArgs {
             args: self.args,
+            abs_timer: self.abs_timer,
             prev: self.now,
             now: PhantomData,
         }
@@ -312,6 +415,18 @@ impl<'prev, 'now> SubmitArgs<'prev, 'now> {
         self
     }
 
+    /// Interpret the [`timespec`](Self::timespec) as an absolute deadline instead of a relative
+    /// timeout. The deadline is measured against the clock registered with
+    /// [`Submitter::register_clock`](crate::Submitter::register_clock), which is
+    /// `CLOCK_MONOTONIC` unless another one has been registered.
+    ///
+    /// Available since 6.12.
+    #[inline]
+    pub fn abs_timer(mut self) -> Self {
+        self.abs_timer = true;
+        self
+    }
+
     #[inline]
     /// Timeout for submit operation
     pub fn timespec<'new>(mut self, timespec: &'new Timespec) -> SubmitArgs<'now, 'new> {
@@ -319,12 +434,117 @@ impl<'prev, 'now> SubmitArgs<'prev, 'now> {
 
         SubmitArgs {
             args: self.args,
+            abs_timer: self.abs_timer,
             prev: self.now,
             now: PhantomData,
         }
     }
 }
 
+/// A slot of a [`RegWaitRegion`], describing how to wait for completions when passed to
+/// [`Submitter::submit_with_registered_args`](crate::Submitter::submit_with_registered_args).
+///
+/// Slots start out zeroed, which waits without a timeout.
+#[repr(transparent)]
+pub struct RegWait(sys::io_uring_reg_wait);
+
+impl RegWait {
+    /// Sets the timeout for waiting, or clears it if `None`.
+    pub fn set_timespec(&mut self, timespec: Option<Timespec>) {
+        match timespec {
+            Some(ts) => {
+                self.0.ts = ts.0;
+                self.0.flags |= sys::IORING_REG_WAIT_TS;
+            }
+            None => {
+                self.0.ts = Timespec::new().0;
+                self.0.flags &= !sys::IORING_REG_WAIT_TS;
+            }
+        }
+    }
+
+    /// Sets a timeout in microseconds to start waiting for a minimum of a single completion, see
+    /// [`SubmitArgs::min_wait_usec`].
+    pub fn set_min_wait_usec(&mut self, min_wait_usec: u32) {
+        self.0.min_wait_usec = min_wait_usec;
+    }
+
+    /// Returns the timeout for waiting, if any.
+    pub fn timespec(&self) -> Option<Timespec> {
+        if self.0.flags & sys::IORING_REG_WAIT_TS != 0 {
+            Some(Timespec(self.0.ts))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the minimum wait timeout in microseconds.
+    pub fn min_wait_usec(&self) -> u32 {
+        self.0.min_wait_usec
+    }
+}
+
+/// An array of [`RegWait`] slots in memory registered with the kernel by
+/// [`Submitter::register_mem_region`](crate::Submitter::register_mem_region).
+///
+/// Waiting with a registered slot saves copying the wait arguments into the kernel on every
+/// `io_uring_enter` call. The kernel keeps the memory pinned until the ring is destroyed, so
+/// dropping the region early is safe, but its slots can no longer be updated.
+pub struct RegWaitRegion {
+    mmap: Mmap,
+    nr: usize,
+}
+
+impl RegWaitRegion {
+    pub(crate) fn new(nr: u32) -> io::Result<RegWaitRegion> {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let size = (nr as usize * mem::size_of::<RegWait>()).max(1);
+        let size = (size + page_size - 1) & !(page_size - 1);
+
+        Ok(RegWaitRegion {
+            mmap: Mmap::new_anon(size)?,
+            nr: nr as usize,
+        })
+    }
+
+    pub(crate) fn as_desc(&self) -> sys::io_uring_region_desc {
+        sys::io_uring_region_desc {
+            user_addr: self.mmap.as_mut_ptr() as _,
+            size: self.mmap.len() as _,
+            flags: sys::IORING_MEM_REGION_TYPE_USER,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the number of slots in the region.
+    pub fn len(&self) -> usize {
+        self.nr
+    }
+
+    /// Returns `true` if the region has no slots.
+    pub fn is_empty(&self) -> bool {
+        self.nr == 0
+    }
+
+    /// Returns the slots of the region.
+    pub fn slots(&self) -> &[RegWait] {
+        unsafe { slice::from_raw_parts(self.mmap.as_mut_ptr().cast(), self.nr) }
+    }
+
+    /// Returns the slots of the region, which can be updated between waits.
+    pub fn slots_mut(&mut self) -> &mut [RegWait] {
+        unsafe { slice::from_raw_parts_mut(self.mmap.as_mut_ptr().cast(), self.nr) }
+    }
+}
+
+impl fmt::Debug for RegWaitRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegWaitRegion")
+            .field("len", &self.nr)
+            .finish()
+    }
+}
+
 #[repr(transparent)]
 pub struct BufRingEntry(sys::io_uring_buf);
 
@@ -422,6 +642,252 @@ impl DestinationSlot {
     }
 }
 
+/// An owned socket address, for requests such as [`Connect`](crate::opcode::Connect),
+/// [`Bind`](crate::opcode::Bind) and [`Accept`](crate::opcode::Accept).
+///
+/// Requests only point at the address, so the builders take it pinned, such as with
+/// `Box::pin`: it must not move, and must not be dropped until the requests have completed. Once
+/// an `Accept` has completed, the address of the peer can be read back through the same pinned
+/// address with [`as_socket_addr`](Self::as_socket_addr) or [`as_unix_addr`](Self::as_unix_addr).
+///
+/// ```
+/// use io_uring::types::SockAddr;
+///
+/// let addr = Box::pin(SockAddr::from(
+///     "127.0.0.1:8080".parse::<std::net::SocketAddr>().unwrap(),
+/// ));
+/// assert_eq!(addr.as_socket_addr(), Some("127.0.0.1:8080".parse().unwrap()));
+/// ```
+#[derive(Clone)]
+pub struct SockAddr {
+    storage: libc::sockaddr_storage,
+    len: libc::socklen_t,
+    _pinned: PhantomPinned,
+}
+
+impl SockAddr {
+    // The offset of `sun_path` in `sockaddr_un`.
+    const SUN_PATH_OFFSET: usize = mem::size_of::<libc::sa_family_t>();
+
+    /// Create an empty address, with room for any kind of address, for a request to fill in.
+    pub fn new() -> SockAddr {
+        SockAddr {
+            storage: unsafe { mem::zeroed() },
+            len: mem::size_of::<libc::sockaddr_storage>() as _,
+            _pinned: PhantomPinned,
+        }
+    }
+
+    /// Create the address of a Unix domain socket bound to `path`.
+    ///
+    /// Fails with [`InvalidInput`](io::ErrorKind::InvalidInput) if the path does not fit, or
+    /// contains a nul byte.
+    pub fn unix<P: AsRef<std::path::Path>>(path: P) -> io::Result<SockAddr> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = path.as_ref().as_os_str().as_bytes();
+
+        let mut addr = SockAddr::new();
+        let sun = addr.storage_mut::<libc::sockaddr_un>();
+        // Leave room for the nul terminator.
+        if path.len() >= sun.sun_path.len() || path.contains(&0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "path must be shorter than SUN_LEN and contain no nul bytes",
+            ));
+        }
+
+        sun.sun_family = libc::AF_UNIX as _;
+        for (dst, &src) in sun.sun_path.iter_mut().zip(path) {
+            *dst = src as _;
+        }
+        addr.len = (Self::SUN_PATH_OFFSET + path.len() + 1) as _;
+
+        Ok(addr)
+    }
+
+    /// The address family, such as `AF_INET`.
+    pub fn family(&self) -> libc::sa_family_t {
+        self.storage.ss_family
+    }
+
+    /// A pointer to the address, to pass along with [`len`](Self::len) to requests that read it.
+    pub fn as_ptr(&self) -> *const libc::sockaddr {
+        cast_ptr(&self.storage).cast()
+    }
+
+    /// A pointer to the address, for requests that write it.
+    pub fn as_mut_ptr(self: Pin<&mut Self>) -> *mut libc::sockaddr {
+        // Safety: the address is written in place, never moved.
+        let this = unsafe { self.get_unchecked_mut() };
+        (&mut this.storage as *mut libc::sockaddr_storage).cast()
+    }
+
+    /// The length of the address.
+    pub fn len(&self) -> libc::socklen_t {
+        self.len
+    }
+
+    /// Whether the address is empty, as a fresh [`SockAddr::new`] is until it is filled in.
+    pub fn is_empty(&self) -> bool {
+        self.family() == libc::AF_UNSPEC as libc::sa_family_t
+    }
+
+    /// A pointer to the length of the address, for requests that write the address and its
+    /// length. The length is reset to the whole capacity first, as the kernel expects.
+    pub fn len_mut_ptr(self: Pin<&mut Self>) -> *mut libc::socklen_t {
+        // Safety: the length is written in place, never moved.
+        let this = unsafe { self.get_unchecked_mut() };
+        this.len = mem::size_of::<libc::sockaddr_storage>() as _;
+        &mut this.len
+    }
+
+    /// Point the `msg_name` of `msg` at this address, for [`SendMsg`](crate::opcode::SendMsg)
+    /// and [`SendMsgZc`](crate::opcode::SendMsgZc).
+    pub fn apply_to_msghdr(&self, msg: &mut libc::msghdr) {
+        msg.msg_name = self.as_ptr() as *mut libc::c_void;
+        msg.msg_namelen = self.len;
+    }
+
+    /// The address as an IPv4 or IPv6 socket address, or `None` if it is neither.
+    pub fn as_socket_addr(&self) -> Option<std::net::SocketAddr> {
+        use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+        match self.family() as libc::c_int {
+            libc::AF_INET => {
+                let sin = self.storage_ref::<libc::sockaddr_in>();
+                Some(
+                    SocketAddrV4::new(
+                        Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)),
+                        u16::from_be(sin.sin_port),
+                    )
+                    .into(),
+                )
+            }
+            libc::AF_INET6 => {
+                let sin6 = self.storage_ref::<libc::sockaddr_in6>();
+                Some(
+                    SocketAddrV6::new(
+                        Ipv6Addr::from(sin6.sin6_addr.s6_addr),
+                        u16::from_be(sin6.sin6_port),
+                        sin6.sin6_flowinfo,
+                        sin6.sin6_scope_id,
+                    )
+                    .into(),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    /// The address as a Unix domain socket address, or `None` if it is not one.
+    ///
+    /// Addresses in the abstract namespace are not supported, and are returned as `None` as well.
+    pub fn as_unix_addr(&self) -> Option<std::os::unix::net::SocketAddr> {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::net::SocketAddr;
+
+        if self.family() as libc::c_int != libc::AF_UNIX {
+            return None;
+        }
+
+        let sun = self.storage_ref::<libc::sockaddr_un>();
+        let len = (self.len as usize).saturating_sub(Self::SUN_PATH_OFFSET);
+        let path = &sun.sun_path[..len.min(sun.sun_path.len())];
+        match path.first() {
+            // The address of an unbound socket, which std builds from an empty path.
+            None => SocketAddr::from_pathname("").ok(),
+            Some(0) => None,
+            Some(_) => {
+                let path: Vec<u8> = path
+                    .iter()
+                    .take_while(|&&c| c != 0)
+                    .map(|&c| c as u8)
+                    .collect();
+                SocketAddr::from_pathname(std::ffi::OsStr::from_bytes(&path)).ok()
+            }
+        }
+    }
+
+    fn storage_ref<T>(&self) -> &T {
+        debug_assert!(mem::size_of::<T>() <= mem::size_of::<libc::sockaddr_storage>());
+        unsafe { &*cast_ptr(&self.storage).cast::<T>() }
+    }
+
+    fn storage_mut<T>(&mut self) -> &mut T {
+        debug_assert!(mem::size_of::<T>() <= mem::size_of::<libc::sockaddr_storage>());
+        unsafe { &mut *(&mut self.storage as *mut libc::sockaddr_storage).cast::<T>() }
+    }
+}
+
+impl Default for SockAddr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<std::net::SocketAddr> for SockAddr {
+    fn from(addr: std::net::SocketAddr) -> SockAddr {
+        let mut sock_addr = SockAddr::new();
+
+        match addr {
+            std::net::SocketAddr::V4(addr) => {
+                let sin = sock_addr.storage_mut::<libc::sockaddr_in>();
+                sin.sin_family = libc::AF_INET as _;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+                sock_addr.len = mem::size_of::<libc::sockaddr_in>() as _;
+            }
+            std::net::SocketAddr::V6(addr) => {
+                let sin6 = sock_addr.storage_mut::<libc::sockaddr_in6>();
+                sin6.sin6_family = libc::AF_INET6 as _;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.sin6_addr.s6_addr = addr.ip().octets();
+                sin6.sin6_scope_id = addr.scope_id();
+                sock_addr.len = mem::size_of::<libc::sockaddr_in6>() as _;
+            }
+        }
+
+        sock_addr
+    }
+}
+
+impl TryFrom<&std::os::unix::net::SocketAddr> for SockAddr {
+    type Error = io::Error;
+
+    /// Fails with [`InvalidInput`](io::ErrorKind::InvalidInput) for addresses in the abstract
+    /// namespace, which are not supported.
+    fn try_from(addr: &std::os::unix::net::SocketAddr) -> io::Result<SockAddr> {
+        if let Some(path) = addr.as_pathname() {
+            SockAddr::unix(path)
+        } else if addr.is_unnamed() {
+            let mut sock_addr = SockAddr::new();
+            sock_addr.storage.ss_family = libc::AF_UNIX as _;
+            sock_addr.len = Self::SUN_PATH_OFFSET as _;
+            Ok(sock_addr)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "abstract socket addresses are not supported",
+            ))
+        }
+    }
+}
+
+impl fmt::Debug for SockAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("SockAddr");
+        f.field("family", &self.family());
+        if let Some(addr) = self.as_socket_addr() {
+            f.field("addr", &addr);
+        } else if let Some(addr) = self.as_unix_addr() {
+            f.field("addr", &addr);
+        }
+        f.field("len", &self.len).finish()
+    }
+}
+
 /// Helper structure for parsing the result of a multishot [`opcode::RecvMsg`](crate::opcode::RecvMsg).
 #[derive(Debug)]
 pub struct RecvMsgOut<'buf> {
@@ -582,7 +1048,8 @@ impl<'buf> RecvMsgOut<'buf> {
 /// [CancelBuilder] constructs match criteria for request cancellation.
 ///
 /// The [CancelBuilder] can be used to selectively cancel one or more requests
-/// by user_data, fd, fixed fd, or unconditionally.
+/// by user_data, fd, fixed fd, opcode, or unconditionally. The criteria other than
+/// [CancelBuilder::any] can be combined, in which case a request has to match all of them.
 ///
 /// ### Examples
 ///
@@ -603,18 +1070,25 @@ impl<'buf> RecvMsgOut<'buf> {
 ///
 /// // Match all in-flight requests with user_data = 42.
 /// CancelBuilder::user_data(42).all();
+///
+/// // Match all in-flight multishot receives on fd = 42.
+/// CancelBuilder::fd(Fd(42))
+///     .with_opcode(io_uring::opcode::RecvMulti::CODE)
+///     .all();
 /// ```
 #[derive(Debug)]
 pub struct CancelBuilder {
     pub(crate) flags: AsyncCancelFlags,
     pub(crate) user_data: Option<u64>,
     pub(crate) fd: Option<sealed::Target>,
+    pub(crate) opcode: Option<u8>,
 }
 
 impl CancelBuilder {
     /// Create a new [CancelBuilder] which will match any in-flight request.
     ///
-    /// This will cancel every in-flight request in the ring.
+    /// This will cancel every in-flight request in the ring. It cannot be combined with other
+    /// criteria, the kernel rejects that with `EINVAL`.
     ///
     /// Async cancellation matching any requests is only available since 5.19.
     pub const fn any() -> Self {
@@ -622,6 +1096,7 @@ impl CancelBuilder {
             flags: AsyncCancelFlags::ANY,
             user_data: None,
             fd: None,
+            opcode: None,
         }
     }
 
@@ -636,6 +1111,7 @@ impl CancelBuilder {
             flags: AsyncCancelFlags::empty(),
             user_data: Some(user_data),
             fd: None,
+            opcode: None,
         }
     }
 
@@ -647,18 +1123,62 @@ impl CancelBuilder {
     ///
     /// FD async cancellation is only available since 5.19.
     pub fn fd(fd: impl sealed::UseFixed) -> Self {
-        let mut flags = AsyncCancelFlags::FD;
-        let target = fd.into();
-        if matches!(target, sealed::Target::Fixed(_)) {
-            flags.insert(AsyncCancelFlags::FD_FIXED);
+        Self {
+            flags: AsyncCancelFlags::empty(),
+            user_data: None,
+            fd: None,
+            opcode: None,
         }
+        .with_fd(fd)
+    }
+
+    /// Create a new [CancelBuilder] which will match in-flight requests with
+    /// the given `opcode`, such as [`RecvMulti::CODE`](crate::opcode::RecvMulti::CODE).
+    ///
+    /// The first request with the given opcode will be canceled. [CancelBuilder::all](#method.all)
+    /// can be called to instead match every request with the provided opcode.
+    ///
+    /// Opcode async cancellation is only available since 6.6.
+    pub const fn opcode(opcode: u8) -> Self {
         Self {
-            flags,
+            flags: AsyncCancelFlags::OP,
             user_data: None,
-            fd: Some(target),
+            fd: None,
+            opcode: Some(opcode),
         }
     }
 
+    /// Modify the [CancelBuilder] match criteria to also require the given `user_data` value.
+    ///
+    /// Matching on the user_data along with the fd or opcode is only available since 6.6.
+    pub const fn with_user_data(mut self, user_data: u64) -> Self {
+        self.user_data = Some(user_data);
+        self
+    }
+
+    /// Modify the [CancelBuilder] match criteria to also require the given `fd` value.
+    ///
+    /// FD async cancellation is only available since 5.19.
+    pub fn with_fd(mut self, fd: impl sealed::UseFixed) -> Self {
+        let target = fd.into();
+        self.flags.insert(AsyncCancelFlags::FD);
+        self.flags.set(
+            AsyncCancelFlags::FD_FIXED,
+            matches!(target, sealed::Target::Fixed(_)),
+        );
+        self.fd = Some(target);
+        self
+    }
+
+    /// Modify the [CancelBuilder] match criteria to also require the given `opcode`.
+    ///
+    /// Opcode async cancellation is only available since 6.6.
+    pub fn with_opcode(mut self, opcode: u8) -> Self {
+        self.flags.insert(AsyncCancelFlags::OP);
+        self.opcode = Some(opcode);
+        self
+    }
+
     /// Modify the [CancelBuilder] match criteria to match all in-flight requests
     /// rather than just the first one.
     ///
@@ -670,6 +1190,18 @@ impl CancelBuilder {
         self
     }
 
+    pub(crate) fn to_flags(&self) -> u32 {
+        let mut flags = self.flags;
+        // The user_data is only matched on by default if neither the fd nor the opcode is, so
+        // ask for it explicitly when combined with them. Leave the flag out otherwise, so that
+        // kernels predating it accept the request.
+        if self.user_data.is_some() && flags.intersects(AsyncCancelFlags::FD | AsyncCancelFlags::OP)
+        {
+            flags.insert(AsyncCancelFlags::USERDATA);
+        }
+        flags.bits()
+    }
+
     pub(crate) fn to_fd(&self) -> i32 {
         self.fd
             .as_ref()
@@ -845,4 +1377,50 @@ mod tests {
             AsyncCancelFlags::FD | AsyncCancelFlags::FD_FIXED | AsyncCancelFlags::ALL
         );
     }
+
+    #[test]
+    fn test_sock_addr_conversions() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let sock_addr = SockAddr::from(addr);
+        assert_eq!(sock_addr.family(), libc::AF_INET as libc::sa_family_t);
+        assert_eq!(
+            sock_addr.len() as usize,
+            mem::size_of::<libc::sockaddr_in>()
+        );
+        assert_eq!(sock_addr.as_socket_addr(), Some(addr));
+        assert!(sock_addr.as_unix_addr().is_none());
+
+        let addr: std::net::SocketAddr = "[fe80::1%2]:443".parse().unwrap();
+        let sock_addr = SockAddr::from(addr);
+        assert_eq!(sock_addr.family(), libc::AF_INET6 as libc::sa_family_t);
+        assert_eq!(sock_addr.as_socket_addr(), Some(addr));
+
+        let sock_addr = SockAddr::unix("/tmp/socket").unwrap();
+        assert_eq!(sock_addr.family(), libc::AF_UNIX as libc::sa_family_t);
+        assert_eq!(
+            sock_addr.len() as usize,
+            mem::size_of::<libc::sa_family_t>() + "/tmp/socket".len() + 1
+        );
+        let unix_addr = sock_addr.as_unix_addr().unwrap();
+        assert_eq!(
+            unix_addr.as_pathname(),
+            Some(std::path::Path::new("/tmp/socket"))
+        );
+        assert_eq!(
+            SockAddr::try_from(&unix_addr).unwrap().len(),
+            sock_addr.len()
+        );
+        assert!(sock_addr.as_socket_addr().is_none());
+
+        let too_long = "x".repeat(108);
+        assert_eq!(
+            SockAddr::unix(too_long).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        let sock_addr = SockAddr::new();
+        assert!(sock_addr.is_empty());
+        assert!(sock_addr.as_socket_addr().is_none());
+        assert!(sock_addr.as_unix_addr().is_none());
+    }
 }
@@ -1,7 +1,7 @@
 use std::num::NonZeroU32;
 use std::os::unix::io::AsRawFd;
 use std::sync::atomic;
-use std::{io, ptr};
+use std::{io, ops, ptr};
 
 pub(crate) mod private {
     /// Private trait that we use as a supertrait of `EntryMarker` to prevent it from being
@@ -11,12 +11,52 @@ pub(crate) mod private {
     pub trait Sealed {}
 }
 
-/// A region of memory mapped using `mmap(2)`.
-pub(crate) struct Mmap {
+/// A region of memory shared with the kernel.
+pub(crate) struct Region {
     addr: ptr::NonNull<libc::c_void>,
     len: usize,
 }
 
+impl Region {
+    /// Refer to `len` bytes of memory starting at `addr`, which is not released on drop.
+    ///
+    /// # Safety
+    ///
+    /// The memory must stay valid for as long as the region is used.
+    pub unsafe fn new(addr: ptr::NonNull<libc::c_void>, len: usize) -> Region {
+        Region { addr, len }
+    }
+
+    /// Do not make the stored memory accessible by child processes after a `fork`.
+    pub fn dontfork(&self) -> io::Result<()> {
+        match unsafe { libc::madvise(self.addr.as_ptr(), self.len, libc::MADV_DONTFORK) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Get a pointer to the memory.
+    #[inline]
+    pub fn as_mut_ptr(&self) -> *mut libc::c_void {
+        self.addr.as_ptr()
+    }
+
+    /// Get the length of the memory in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get a pointer to the data at the given offset.
+    #[inline]
+    pub unsafe fn offset(&self, offset: u32) -> *mut libc::c_void {
+        self.as_mut_ptr().add(offset as usize)
+    }
+}
+
+/// A region of memory mapped using `mmap(2)`.
+pub(crate) struct Mmap(Region);
+
 impl Mmap {
     /// Map `len` bytes starting from the offset `offset` in the file descriptor `fd` into memory.
     pub fn new(fd: &OwnedFd, offset: libc::off_t, len: usize) -> io::Result<Mmap> {
@@ -33,37 +73,47 @@ impl Mmap {
                 addr => {
                     // here, `mmap` will never return null
                     let addr = ptr::NonNull::new_unchecked(addr);
-                    Ok(Mmap { addr, len })
+                    Ok(Mmap(Region { addr, len }))
                 }
             }
         }
     }
 
-    /// Do not make the stored memory accessible by child processes after a `fork`.
-    pub fn dontfork(&self) -> io::Result<()> {
-        match unsafe { libc::madvise(self.addr.as_ptr(), self.len, libc::MADV_DONTFORK) } {
-            0 => Ok(()),
-            _ => Err(io::Error::last_os_error()),
+    /// Map `len` bytes of zeroed anonymous memory.
+    pub fn new_anon(len: usize) -> io::Result<Mmap> {
+        unsafe {
+            match libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_POPULATE,
+                -1,
+                0,
+            ) {
+                libc::MAP_FAILED => Err(io::Error::last_os_error()),
+                addr => {
+                    // here, `mmap` will never return null
+                    let addr = ptr::NonNull::new_unchecked(addr);
+                    Ok(Mmap(Region { addr, len }))
+                }
+            }
         }
     }
+}
 
-    /// Get a pointer to the memory.
-    #[inline]
-    pub fn as_mut_ptr(&self) -> *mut libc::c_void {
-        self.addr.as_ptr()
-    }
+impl ops::Deref for Mmap {
+    type Target = Region;
 
-    /// Get a pointer to the data at the given offset.
     #[inline]
-    pub unsafe fn offset(&self, offset: u32) -> *mut libc::c_void {
-        self.as_mut_ptr().add(offset as usize)
+    fn deref(&self) -> &Region {
+        &self.0
     }
 }
 
 impl Drop for Mmap {
     fn drop(&mut self) {
         unsafe {
-            libc::munmap(self.addr.as_ptr(), self.len);
+            libc::munmap(self.0.addr.as_ptr(), self.0.len);
         }
     }
 }